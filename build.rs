@@ -0,0 +1,19 @@
+use std::process::Command;
+
+// 编译时记录 git 提交哈希，供 --version 显示；不在 git 仓库中编译时省略
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output();
+    if let Ok(output) = output {
+        if output.status.success() {
+            let hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !hash.is_empty() {
+                println!("cargo:rustc-env=MOUSE_SMOOTHER_GIT_HASH={}", hash);
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::{
+    Device, DeviceWrapper, InputEvent, ReadFlag, ReadStatus, TimeVal, UInputDevice, UninitDevice,
+};
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::{device_error, Error};
+use crate::utils::{find_mouse_devices, open_device_error, wait_readable};
+use crate::{log_info, log_warn, MouseSmoother};
+
+/// 默认的样本数
+pub const DEFAULT_SAMPLES: usize = 1000;
+
+// 注入事件的合成源设备名称，平滑后的虚拟设备名称由 device.virtual_name 决定
+const SOURCE_NAME: &str = "mouse_smoother benchmark";
+// 等待设备节点出现的时间
+const DEVICE_TIMEOUT: Duration = Duration::from_secs(3);
+// 单个事件最长等待时间，超过即记为丢失
+const SAMPLE_TIMEOUT: Duration = Duration::from_millis(100);
+// 两次注入之间的间隔，避免事件在内核缓冲中排队影响测量
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(2);
+
+/// 延迟样本，按需排序计算分位数
+#[derive(Default)]
+pub struct LatencyHistogram {
+    samples: Vec<Duration>,
+    sorted: bool,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        self.samples.push(latency);
+        self.sorted = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// 第 `p` 百分位（0-100）的延迟，没有样本时返回 None
+    pub fn percentile(&mut self, p: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        if !self.sorted {
+            self.samples.sort_unstable();
+            self.sorted = true;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * (self.samples.len() - 1) as f64).round();
+        Some(self.samples[rank as usize])
+    }
+}
+
+/// 测量事件从进入被平滑的设备到从虚拟设备读出的端到端延迟
+///
+/// 创建一个合成的 uinput 鼠标作为源设备，按正常流程抓取它并创建虚拟设备，
+/// 然后交替注入 REL_X +1/-1，记录每个事件从写入到从虚拟设备读回的时间。
+/// 指针移动不经过消抖，测量的是读取、处理和写入的路径本身；光标会在原地来回移动一个像素。
+pub fn run(config: &Config, samples: usize) -> Result<(), Error> {
+    let source = create_source_device()?;
+    let source_path = match source.devnode() {
+        Some(path) => path.to_string(),
+        None => return Err(Error::DeviceNotFound("无法获取合成设备的设备节点".to_string())),
+    };
+    wait_for_device(SOURCE_NAME)?;
+    log_info!("创建合成设备: {} ({})", SOURCE_NAME, source_path);
+
+    // 基准测试需要真实的虚拟设备
+    let mut config = config.clone();
+    config.overrides.dry_run = false;
+    let config_path = String::new();
+    let output_name = config.virtual_device_name(SOURCE_NAME);
+
+    // 与多设备模式相同，设备在处理线程中打开；合成设备销毁后 run 随之返回
+    let smoother = thread::spawn(move || -> Result<(), Error> {
+        let mut smoother = MouseSmoother::new(&source_path, &config, &config_path)?;
+        smoother.run().map(|_| ())
+    });
+
+    let result = wait_for_device(&output_name)
+        .and_then(|output_path| measure(&source, &output_path, samples));
+
+    drop(source);
+    match smoother.join() {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log_warn!("平滑线程出错: {}", e),
+        Err(_) => log_warn!("平滑线程异常退出"),
+    }
+    result
+}
+
+/// 创建注入事件用的合成鼠标，声明 BTN_LEFT 使其能被当作鼠标扫描到
+fn create_source_device() -> Result<UInputDevice, Error> {
+    let device = UninitDevice::new().ok_or_else(|| io::Error::other("无法创建合成设备"))?;
+    device.set_name(SOURCE_NAME);
+    device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_LEFT), None)?;
+    device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_X), None)?;
+    device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_Y), None)?;
+    device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_WHEEL), None)?;
+    device.enable_event_code(&EventCode::EV_SYN(EV_SYN::SYN_REPORT), None)?;
+    UInputDevice::create_from_device(&device).map_err(|e| device_error("无法创建合成设备", e))
+}
+
+/// 等待指定名称的鼠标设备出现，返回其路径
+fn wait_for_device(name: &str) -> Result<String, Error> {
+    let start = Instant::now();
+    while start.elapsed() < DEVICE_TIMEOUT {
+        if let Some(device) = find_mouse_devices()?.into_iter().find(|d| d.name == name) {
+            return Ok(device.path);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    Err(Error::DeviceNotFound(format!("等待设备 {} 超时", name)))
+}
+
+fn measure(
+    source: &UInputDevice,
+    output_path: &str,
+    samples: usize,
+) -> Result<(), Error> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(output_path)
+        .map_err(|e| open_device_error(output_path, e))?;
+    let output = Device::new_from_file(file).map_err(|e| open_device_error(output_path, e))?;
+    let fd = output.file().as_raw_fd();
+    log_info!("从 {} 读取平滑后的事件，共 {} 个样本", output_path, samples);
+
+    let time = TimeVal::new(0, 0);
+    let mut histogram = LatencyHistogram::default();
+    let mut lost = 0;
+
+    for i in 0..samples {
+        let value = if i % 2 == 0 { 1 } else { -1 };
+        let start = Instant::now();
+        source.write_event(&InputEvent::new(
+            &time,
+            &EventCode::EV_REL(EV_REL::REL_X),
+            value,
+        ))?;
+        source.write_event(&InputEvent::new(
+            &time,
+            &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            0,
+        ))?;
+
+        if wait_for_event(&output, fd, start)? {
+            histogram.record(start.elapsed());
+        } else {
+            lost += 1;
+        }
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+
+    if histogram.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "没有从虚拟设备读回任何事件").into());
+    }
+
+    let micros = |d: Option<Duration>| d.map_or(0, |d| d.as_micros());
+    println!("samples: {}", histogram.len());
+    println!("lost: {}", lost);
+    println!("min_us: {}", micros(histogram.percentile(0.0)));
+    println!("p50_us: {}", micros(histogram.percentile(50.0)));
+    println!("p99_us: {}", micros(histogram.percentile(99.0)));
+    println!("max_us: {}", micros(histogram.percentile(100.0)));
+    Ok(())
+}
+
+/// 等待虚拟设备输出 REL_X，超过 `SAMPLE_TIMEOUT` 返回 false
+fn wait_for_event(
+    output: &Device,
+    fd: RawFd,
+    start: Instant,
+) -> Result<bool, Error> {
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= SAMPLE_TIMEOUT {
+            return Ok(false);
+        }
+        if !wait_readable(fd, Some(SAMPLE_TIMEOUT - elapsed))? {
+            continue;
+        }
+
+        let mut found = false;
+        loop {
+            match output.next_event(ReadFlag::NORMAL) {
+                Ok((ReadStatus::Success, event)) => {
+                    if event.event_code == EventCode::EV_REL(EV_REL::REL_X) {
+                        found = true;
+                    }
+                }
+                Ok((ReadStatus::Sync, _)) => {}
+                Err(e) if e.raw_os_error() == Some(libc::EAGAIN) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if found {
+            return Ok(true);
+        }
+    }
+}
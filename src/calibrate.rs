@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, DeviceProfileConfig};
+use crate::log_info;
+
+// 反向报告前后两次同向滚动的最大间隔，超过时不认为是夹在中间的抖动
+const SPIKE_WINDOW: Duration = Duration::from_millis(300);
+// 给出建议所需的最少抖动样本数
+const MIN_SPIKES: usize = 5;
+// 建议的消抖时间在观察到的抖动间隔之上留出的余量
+const DEBOUNCE_MARGIN: f64 = 1.25;
+// 建议的消抖时间范围（毫秒）
+const MIN_DEBOUNCE_MS: u64 = 10;
+const MAX_DEBOUNCE_MS: u64 = 200;
+
+/// 一次垂直滚轮报告（高分辨率单位）
+#[derive(Clone, Copy)]
+struct Report {
+    time: Instant,
+    value: i32,
+}
+
+/// 校准得到的建议参数
+pub struct Suggestion {
+    pub debounce_time_ms: u64,
+    pub jitter_distance_threshold: i32,
+}
+
+impl Suggestion {
+    /// 把建议值写入匹配条件为 `matcher` 的设备配置，没有时新建一个
+    pub fn apply(&self, config: &mut Config, matcher: &str) {
+        let index = match config
+            .device_profile
+            .iter()
+            .position(|profile| profile.matcher == matcher)
+        {
+            Some(index) => index,
+            None => {
+                config
+                    .device_profile
+                    .push(DeviceProfileConfig::new(matcher.to_string()));
+                config.device_profile.len() - 1
+            }
+        };
+        let profile = &mut config.device_profile[index];
+        profile.debounce_time_ms = Some(self.debounce_time_ms);
+        profile.jitter_distance_threshold = Some(self.jitter_distance_threshold);
+    }
+}
+
+/// 校准模式（`--calibrate`）：观察原始的垂直滚轮报告，统计抖动的特征并给出建议参数
+///
+/// 夹在两次同方向滚动之间、前后间隔不超过 `SPIKE_WINDOW` 的单个反向报告视为抖动，
+/// 记录它与前一次滚动的间隔和它的距离。消抖时间要覆盖抖动间隔才能过滤它们，
+/// 建议值取间隔的 95 百分位再留出余量；抖动距离阈值取距离的 95 百分位。
+#[derive(Default)]
+pub struct Calibrator {
+    previous: Option<Report>,
+    last: Option<Report>,
+    reports: u64,
+    reversals: u64,
+    spike_gaps: Vec<Duration>,
+    spike_distances: Vec<i32>,
+}
+
+impl Calibrator {
+    /// 记录一次滚轮报告，值为 0 的报告忽略
+    pub fn observe(&mut self, value: i32, now: Instant) {
+        if value == 0 {
+            return;
+        }
+        self.reports += 1;
+        let report = Report { time: now, value };
+
+        if let (Some(first), Some(middle)) = (self.previous, self.last) {
+            let direction = first.value.signum();
+            if middle.value.signum() != direction
+                && value.signum() == direction
+                && now.duration_since(first.time) <= SPIKE_WINDOW
+            {
+                self.spike_gaps.push(middle.time.duration_since(first.time));
+                self.spike_distances.push(middle.value.saturating_abs());
+            }
+        }
+        if self
+            .last
+            .is_some_and(|last| last.value.signum() != value.signum())
+        {
+            self.reversals += 1;
+        }
+
+        self.previous = self.last;
+        self.last = Some(report);
+    }
+
+    /// 根据收集到的抖动计算建议参数，抖动样本太少时返回 None
+    pub fn suggest(&self) -> Option<Suggestion> {
+        if self.spike_gaps.len() < MIN_SPIKES {
+            return None;
+        }
+
+        let gap = percentile(&self.spike_gaps, 95.0);
+        let debounce_time_ms = ((gap.as_secs_f64() * 1000.0 * DEBOUNCE_MARGIN).ceil() as u64)
+            .clamp(MIN_DEBOUNCE_MS, MAX_DEBOUNCE_MS);
+        Some(Suggestion {
+            debounce_time_ms,
+            jitter_distance_threshold: percentile(&self.spike_distances, 95.0),
+        })
+    }
+
+    /// 打印统计结果和建议参数（匹配条件为 `matcher` 的设备配置，可以直接粘贴到配置文件）
+    ///
+    /// `config` 是校准时该设备实际使用的配置，用于对比当前值。
+    pub fn print_report(&self, config: &Config, matcher: &str) -> Option<Suggestion> {
+        println!(
+            "# 共 {} 次垂直滚轮报告，{} 次换向，其中 {} 次判断为抖动",
+            self.reports,
+            self.reversals,
+            self.spike_gaps.len()
+        );
+
+        let suggestion = match self.suggest() {
+            Some(suggestion) => suggestion,
+            None => {
+                log_info!(
+                    "观察到的抖动少于 {} 次，无法给出建议；滚轮可能没有抖动，或者校准期间滚动太少",
+                    MIN_SPIKES
+                );
+                return None;
+            }
+        };
+
+        println!("[[device_profile]]");
+        println!("match = \"{}\"", matcher);
+        println!(
+            "debounce_time_ms = {}  # 当前 {}",
+            suggestion.debounce_time_ms, config.wheel.debounce_time_ms
+        );
+        println!(
+            "jitter_distance_threshold = {}  # 当前 {}",
+            suggestion.jitter_distance_threshold, config.wheel.jitter_distance_threshold
+        );
+        Some(suggestion)
+    }
+}
+
+/// 第 `p` 百分位的值（向上取整的秩），`samples` 不能为空
+fn percentile<T: Copy + Ord>(samples: &[T], p: f64) -> T {
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
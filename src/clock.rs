@@ -0,0 +1,47 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// 时间来源，消抖器通过它获取当前时间
+///
+/// 实际运行使用 `RealClock`，编译后就是直接调用 `Instant::now()`；
+/// 离线模拟使用 `MockClock`，由调用方决定每个事件的时间。
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// 系统单调时钟
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// 手动设置的时钟，克隆出的副本共享同一个时间
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Rc<Cell<Instant>>,
+}
+
+impl MockClock {
+    pub fn new(start: Instant) -> Self {
+        MockClock {
+            now: Rc::new(Cell::new(start)),
+        }
+    }
+
+    /// 设置当前时间
+    pub fn set(&self, now: Instant) {
+        self.now.set(now);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
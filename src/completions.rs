@@ -0,0 +1,289 @@
+// 隐藏选项 --generate-completions <shell> 输出的补全脚本。命令行参数是手工解析的，
+// 这里维护一份相同的选项表，在 main 中增加选项时需要同步添加到 OPTIONS
+
+/// 支持的 shell
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// 选项参数的类型，决定补全时提供的候选
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Arg {
+    None,
+    File,
+    Device,
+    LogLevel,
+    Number,
+}
+
+struct Opt {
+    short: Option<char>,
+    long: &'static str,
+    arg: Arg,
+    help: &'static str,
+}
+
+const OPTIONS: &[Opt] = &[
+    Opt {
+        short: Some('l'),
+        long: "list",
+        arg: Arg::None,
+        help: "列出所有可用的鼠标设备",
+    },
+    Opt {
+        short: None,
+        long: "verbose",
+        arg: Arg::None,
+        help: "列出每个设备支持的全部事件",
+    },
+    Opt {
+        short: Some('a'),
+        long: "all",
+        arg: Arg::None,
+        help: "同时平滑所有匹配的鼠标设备",
+    },
+    Opt {
+        short: Some('d'),
+        long: "device",
+        arg: Arg::Device,
+        help: "指定设备编号、路径或 vendor:product",
+    },
+    Opt {
+        short: Some('c'),
+        long: "config",
+        arg: Arg::File,
+        help: "指定配置文件路径",
+    },
+    Opt {
+        short: None,
+        long: "create-config",
+        arg: Arg::None,
+        help: "创建默认配置文件",
+    },
+    Opt {
+        short: None,
+        long: "annotated",
+        arg: Arg::None,
+        help: "写入带注释的配置模板",
+    },
+    Opt {
+        short: None,
+        long: "log-level",
+        arg: Arg::LogLevel,
+        help: "设置日志级别",
+    },
+    Opt {
+        short: Some('q'),
+        long: "quiet",
+        arg: Arg::None,
+        help: "只输出错误",
+    },
+    Opt {
+        short: None,
+        long: "run-for",
+        arg: Arg::Number,
+        help: "运行指定秒数后自动退出",
+    },
+    Opt {
+        short: None,
+        long: "dump-events",
+        arg: Arg::None,
+        help: "以 CSV 格式打印原始滚轮事件",
+    },
+    Opt {
+        short: None,
+        long: "simulate",
+        arg: Arg::File,
+        help: "离线模拟消抖",
+    },
+    Opt {
+        short: None,
+        long: "benchmark",
+        arg: Arg::None,
+        help: "测量端到端延迟",
+    },
+    Opt {
+        short: None,
+        long: "calibrate",
+        arg: Arg::Number,
+        help: "统计滚轮抖动并给出建议参数",
+    },
+    Opt {
+        short: None,
+        long: "save-calibration",
+        arg: Arg::None,
+        help: "把校准结果写入配置文件",
+    },
+    Opt {
+        short: None,
+        long: "show-config",
+        arg: Arg::None,
+        help: "打印实际生效的配置",
+    },
+    Opt {
+        short: None,
+        long: "dry-run",
+        arg: Arg::None,
+        help: "试运行，不抓取设备",
+    },
+    Opt {
+        short: None,
+        long: "debounce-ms",
+        arg: Arg::Number,
+        help: "覆盖垂直滚轮消抖时间",
+    },
+    Opt {
+        short: None,
+        long: "h-debounce-ms",
+        arg: Arg::Number,
+        help: "覆盖水平滚轮消抖时间",
+    },
+    Opt {
+        short: None,
+        long: "timeout-ms",
+        arg: Arg::Number,
+        help: "覆盖消抖超时时间",
+    },
+    Opt {
+        short: None,
+        long: "daemon",
+        arg: Arg::None,
+        help: "转入后台运行",
+    },
+    Opt {
+        short: None,
+        long: "pid-file",
+        arg: Arg::File,
+        help: "后台运行时写入的 PID 文件",
+    },
+    Opt {
+        short: Some('V'),
+        long: "version",
+        arg: Arg::None,
+        help: "显示版本信息",
+    },
+    Opt {
+        short: Some('h'),
+        long: "help",
+        arg: Arg::None,
+        help: "显示帮助信息",
+    },
+];
+
+const LOG_LEVELS: &str = "error warn info debug trace";
+
+/// 生成指定 shell 的补全脚本，不支持的 shell 返回 None
+pub fn generate(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    }
+}
+
+/// 选项的所有写法，例如 `-c|--config`
+fn spellings(opt: &Opt) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some(short) = opt.short {
+        names.push(format!("-{}", short));
+    }
+    names.push(format!("--{}", opt.long));
+    names
+}
+
+/// 参数类型为 `arg` 的选项，bash `case` 分支的模式
+fn bash_pattern(arg: Arg) -> String {
+    OPTIONS
+        .iter()
+        .filter(|opt| opt.arg == arg)
+        .flat_map(spellings)
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn bash() -> String {
+    let words: Vec<String> = OPTIONS.iter().flat_map(spellings).collect();
+    format!(
+        r#"# mouse_smoother 的 bash 补全
+_mouse_smoother() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    case "$prev" in
+        {files})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return
+            ;;
+        {device})
+            COMPREPLY=($(compgen -f -- "${{cur:-/dev/input/}}"))
+            return
+            ;;
+        {log_level})
+            COMPREPLY=($(compgen -W "{levels}" -- "$cur"))
+            return
+            ;;
+        {number})
+            return
+            ;;
+    esac
+
+    COMPREPLY=($(compgen -W "{words}" -- "$cur"))
+}}
+complete -F _mouse_smoother mouse_smoother
+"#,
+        files = bash_pattern(Arg::File),
+        device = bash_pattern(Arg::Device),
+        log_level = bash_pattern(Arg::LogLevel),
+        number = bash_pattern(Arg::Number),
+        levels = LOG_LEVELS,
+        words = words.join(" "),
+    )
+}
+
+fn zsh() -> String {
+    let mut script = String::from("#compdef mouse_smoother\n\n_arguments \\\n");
+    for opt in OPTIONS {
+        let names = match opt.short {
+            Some(short) => format!(
+                "'(-{short} --{long})'{{-{short},--{long}}}",
+                short = short,
+                long = opt.long
+            ),
+            None => format!("--{}", opt.long),
+        };
+        let action = match opt.arg {
+            Arg::None => "",
+            Arg::File => ":文件:_files",
+            Arg::Device => ":设备:_files -W /dev/input",
+            Arg::LogLevel => ":级别:(error warn info debug trace)",
+            Arg::Number => ":数值: ",
+        };
+        // 短选项的写法已经带引号，只给说明和参数部分加引号
+        script.push_str(&format!("  {}'[{}]{}' \\\n", names, opt.help, action));
+    }
+    // 去掉最后一行的续行符
+    script.truncate(script.len() - " \\\n".len());
+    script.push('\n');
+    script
+}
+
+fn fish() -> String {
+    let mut script = String::from("# mouse_smoother 的 fish 补全\ncomplete -c mouse_smoother -f\n");
+    for opt in OPTIONS {
+        let mut line = String::from("complete -c mouse_smoother");
+        if let Some(short) = opt.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", opt.long));
+        match opt.arg {
+            Arg::None => {}
+            Arg::File | Arg::Device => line.push_str(" -r -F"),
+            Arg::LogLevel => line.push_str(&format!(" -x -a '{}'", LOG_LEVELS)),
+            Arg::Number => line.push_str(" -x"),
+        }
+        line.push_str(&format!(" -d '{}'", opt.help));
+        script.push_str(&line);
+        script.push('\n');
+    }
+    script
+}
@@ -1,10 +1,19 @@
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::{log_info, log_warn};
+use crate::error::Error;
+use crate::i18n::Language;
+use crate::logger::LogLevel;
+use crate::utils::DevicePattern;
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     // 设备配置
     #[serde(default)]
@@ -14,23 +23,265 @@ pub struct Config {
     #[serde(default)]
     pub wheel: WheelConfig,
     
+    // 按键配置
+    #[serde(default)]
+    pub button: ButtonConfig,
+    
     // 日志配置
     #[serde(default)]
     pub logging: LoggingConfig,
+    
+    // 指标服务配置
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    
+    // 控制套接字配置
+    #[serde(default)]
+    pub ipc: IpcConfig,
+    
+    // 调试配置
+    #[serde(default)]
+    pub debug: DebugConfig,
+    
+    // 按当前焦点窗口切换的应用配置，按顺序匹配第一个
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<ProfileConfig>,
+    
+    // 按设备区分的滚轮参数，按顺序匹配第一个
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub device_profile: Vec<DeviceProfileConfig>,
+    
+    // 滚轮按键绑定，按住修饰键的绑定优先于没有修饰键的绑定
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wheel_binding: Vec<WheelBindingConfig>,
+    
+    // 命令行覆盖参数，不来自配置文件
+    #[serde(skip)]
+    pub overrides: ConfigOverrides,
+}
+
+/// 命令行中覆盖配置文件的参数，优先级：命令行 > 配置文件 > 默认值
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub debounce_time_ms: Option<u64>,
+    pub h_debounce_time_ms: Option<u64>,
+    pub debounce_timeout_ms: Option<u64>,
+    // 试运行：不抓取设备，不创建虚拟设备
+    pub dry_run: bool,
+}
+
+/// 应用配置：焦点窗口的 WM_CLASS 匹配时覆盖基础配置中的滚轮参数，未设置的项沿用基础配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    // 窗口类名（WM_CLASS 的实例名或类名，不区分大小写），例如 "firefox"
+    pub window_class: String,
+    
+    #[serde(default)]
+    pub debounce_time_ms: Option<u64>,
+    
+    #[serde(default)]
+    pub h_debounce_time_ms: Option<u64>,
+    
+    #[serde(default)]
+    pub debounce_timeout_ms: Option<u64>,
+    
+    #[serde(default)]
+    pub multiplier: Option<f64>,
+}
+
+impl ProfileConfig {
+    /// 是否匹配焦点窗口的任一类名
+    pub fn matches(&self, window_classes: &[String]) -> bool {
+        window_classes
+            .iter()
+            .any(|class| class.eq_ignore_ascii_case(&self.window_class))
+    }
+}
+
+/// 设备配置：接管的设备匹配时覆盖 `[wheel]` 中的参数，未设置的项沿用 `[wheel]`
+///
+/// 应用配置和命令行参数仍然在设备配置之上生效。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceProfileConfig {
+    // 匹配的设备：名称子串（区分大小写）或 vendor:product，与 device.include 的写法相同
+    #[serde(rename = "match")]
+    pub matcher: String,
+    
+    #[serde(default)]
+    pub debounce_time_ms: Option<u64>,
+    
+    #[serde(default)]
+    pub h_debounce_time_ms: Option<u64>,
+    
+    #[serde(default)]
+    pub debounce_timeout_ms: Option<u64>,
+    
+    #[serde(default)]
+    pub jitter_distance_threshold: Option<i32>,
+    
+    #[serde(default)]
+    pub h_jitter_distance_threshold: Option<i32>,
+    
+    #[serde(default)]
+    pub hi_res_step: Option<i32>,
+    
+    #[serde(default)]
+    pub multiplier: Option<f64>,
+    
+    #[serde(default)]
+    pub invert_vertical: Option<bool>,
+    
+    #[serde(default)]
+    pub invert_horizontal: Option<bool>,
+}
+
+impl DeviceProfileConfig {
+    /// 只有匹配条件、没有覆盖任何参数的设备配置
+    pub fn new(matcher: String) -> Self {
+        DeviceProfileConfig {
+            matcher,
+            debounce_time_ms: None,
+            h_debounce_time_ms: None,
+            debounce_timeout_ms: None,
+            jitter_distance_threshold: None,
+            h_jitter_distance_threshold: None,
+            hi_res_step: None,
+            multiplier: None,
+            invert_vertical: None,
+            invert_horizontal: None,
+        }
+    }
+    
+    /// 是否匹配名称为 `name`、ID 为 `vendor:product` 的设备
+    pub fn matches(&self, name: &str, vendor: u16, product: u16) -> bool {
+        DevicePattern::parse(&self.matcher).matches_device(name, vendor, product)
+    }
+}
+
+/// 滚轮轴
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WheelAxis {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+/// 滚轮按键绑定：按住修饰键时（没有设置修饰键时始终）把一个轴上平滑后的滚动
+/// 按刻度转换成按键，每个刻度发送一次按下和松开
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WheelBindingConfig {
+    // 滚轮轴: vertical, horizontal
+    #[serde(default)]
+    pub axis: WheelAxis,
+    
+    // 修饰键（可选），例如 "BTN_SIDE"。与 button.scroll_modifier 一样，修饰键本身不转发，
+    // 按住期间没有滚动就松开时补发一次点击
+    #[serde(default)]
+    pub modifier: Option<String>,
+    
+    // 向上（水平滚轮为向右）滚动一个刻度时发送的按键，例如 "KEY_VOLUMEUP"
+    pub up: String,
+    
+    // 向下（水平滚轮为向左）滚动一个刻度时发送的按键，例如 "KEY_VOLUMEDOWN"
+    pub down: String,
+}
+
+/// 解析后的滚轮按键绑定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WheelBinding {
+    pub horizontal: bool,
+    pub modifier: Option<EV_KEY>,
+    pub up: EV_KEY,
+    pub down: EV_KEY,
+}
+
+impl WheelBindingConfig {
+    /// 解析按键名
+    pub fn codes(&self) -> Result<WheelBinding, String> {
+        let parse = |name: &String| {
+            name.parse::<EV_KEY>()
+                .map_err(|_| format!("wheel_binding 中的按键名无效: '{}'", name))
+        };
+        Ok(WheelBinding {
+            horizontal: self.axis == WheelAxis::Horizontal,
+            modifier: self.modifier.as_ref().map(parse).transpose()?,
+            up: parse(&self.up)?,
+            down: parse(&self.down)?,
+        })
+    }
+}
+
+/// 设备名称过滤器
+///
+/// 配置中可以写成一个字符串（名称包含该字符串）、字符串列表（包含其中任意一个）
+/// 或 `{ regex = "..." }`（名称匹配正则表达式，部分匹配即可，需要整体匹配时用 `^...$`）。
+/// 子串匹配区分大小写。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum NameFilter {
+    Substring(String),
+    AnyOf(Vec<String>),
+    Regex { regex: String },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct DeviceConfig {
-    // 设备路径或ID
+    // 设备路径、编号或 vendor:product
     #[serde(default)]
     pub path: Option<String>,
     
-    // 设备名称过滤器
+    // 设备名称过滤器: 子串、子串列表或 { regex = "..." }
     #[serde(default)]
-    pub name_filter: Option<String>,
+    pub name_filter: Option<NameFilter>,
+    
+    // 只接管匹配其中任意一项的设备，每项为名称子串或 vendor:product；为空时不限制
+    #[serde(default)]
+    pub include: Vec<String>,
+    
+    // 不接管匹配其中任意一项的设备，优先于 include
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    
+    // 左手模式：交换左右键
+    #[serde(default)]
+    pub left_handed: bool,
+    
+    // 是否同时接管触摸板（有 BTN_TOOL_FINGER 的非触摸屏设备），平滑其内核产生的滚轮事件
+    #[serde(default)]
+    pub include_touchpads: bool,
+    
+    // 只接管有相对轴（REL_X/REL_Y、REL_WHEEL 或 REL_DIAL）的设备，避免误抓带 BTN_LEFT 的键盘
+    #[serde(default = "default_require_rel_axes")]
+    pub require_rel_axes: bool,
+    
+    // 是否独占抓取设备。关闭后原设备的事件照常送达其他程序，虚拟设备只发送滚轮的修正量（合并模式）
+    #[serde(default = "default_grab")]
+    pub grab: bool,
+    
+    // 虚拟设备名称（可选），默认为 "Virtual <设备名>"；其中的 {name} 替换为原设备名
+    #[serde(default)]
+    pub virtual_name: Option<String>,
+    
+    // 设备断开后重新扫描的间隔（毫秒），0 表示不重连，直接退出
+    #[serde(default = "default_reconnect_interval")]
+    pub reconnect_interval_ms: u64,
+    
+    // 设备报告可读却持续读不到事件超过此时间（毫秒）时视为设备失效，释放后重新连接；0 表示不检测
+    #[serde(default = "default_stall_timeout")]
+    pub stall_timeout_ms: u64,
+    
+    // 不转发的事件代码（按键或相对轴名称，例如 "BTN_TASK"），合并模式下不起作用
+    #[serde(default)]
+    pub suppress_codes: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct WheelConfig {
     // 垂直滚轮消抖时间（毫秒）
     #[serde(default = "default_debounce_time")]
@@ -40,16 +291,329 @@ pub struct WheelConfig {
     #[serde(default = "default_debounce_time")]
     pub h_debounce_time_ms: u64,
     
+    // 旋钮（REL_DIAL，音量旋钮、飞梭轮）消抖时间（毫秒），0 表示不消抖、原样转发
+    #[serde(default)]
+    pub dial_debounce_time_ms: u64,
+    
+    // 垂直滚轮向上/向下滚动时的消抖时间（毫秒，可选），未设置时使用 debounce_time_ms。
+    // 按正在进行的滚动方向选用：向上滚动过程中的反向抖动按 debounce_time_up_ms 判断
+    #[serde(default)]
+    pub debounce_time_up_ms: Option<u64>,
+    
+    #[serde(default)]
+    pub debounce_time_down_ms: Option<u64>,
+    
     // 滚动超时时间（毫秒）- 超过此时间认为是新的滚动开始
     #[serde(default = "default_scroll_timeout")]
     pub debounce_timeout_ms: u64,
+    
+    // 连续滚动窗口（毫秒）- 间隔超过消抖时间但不超过此值的事件仍视为同一次滚动的延续，
+    // 保留方向跟踪，慢速滚动时的反向抖动也会被过滤；0 表示不启用
+    #[serde(default)]
+    pub continuation_window_ms: u64,
+    
+    // 每个滚轮刻度对应的高分辨率单位数，用于标准/高分辨率事件之间的换算
+    #[serde(default = "default_hi_res_step")]
+    pub hi_res_step: i32,
+    
+    // 垂直滚轮抖动距离阈值（高分辨率单位）- 超时后的反向滚动不超过此距离仍视为抖动
+    #[serde(default = "default_jitter_distance_threshold")]
+    pub jitter_distance_threshold: i32,
+    
+    // 水平滚轮抖动距离阈值（高分辨率单位）
+    #[serde(default = "default_jitter_distance_threshold")]
+    pub h_jitter_distance_threshold: i32,
+    
+    // 旋钮抖动距离阈值（格数）。旋钮没有高分辨率轴，旋钮的距离类参数都以格数为单位
+    #[serde(default = "default_dial_jitter_distance_threshold")]
+    pub dial_jitter_distance_threshold: i32,
+    
+    // 垂直滚轮向上/向下滚动时的抖动距离阈值（可选），未设置时使用 jitter_distance_threshold
+    #[serde(default)]
+    pub jitter_distance_threshold_up: Option<i32>,
+    
+    #[serde(default)]
+    pub jitter_distance_threshold_down: Option<i32>,
+    
+    // 垂直滚轮直通阈值（高分辨率单位）- 单次滚动值的绝对值超过此值时绕过消抖直接发送，0 表示不启用
+    #[serde(default)]
+    pub passthrough_threshold: i32,
+    
+    // 水平滚轮直通阈值（高分辨率单位）
+    #[serde(default)]
+    pub h_passthrough_threshold: i32,
+    
+    // 旋钮直通阈值（格数）
+    #[serde(default)]
+    pub dial_passthrough_threshold: i32,
+    
+    // 垂直滚轮死区（高分辨率单位）- 绝对值小于此值的滚动先累积，累积量达到此值时再一起发送，0 表示不启用
+    #[serde(default)]
+    pub deadzone: i32,
+    
+    // 水平滚轮死区（高分辨率单位）
+    #[serde(default)]
+    pub h_deadzone: i32,
+    
+    // 旋钮死区（格数）
+    #[serde(default)]
+    pub dial_deadzone: i32,
+    
+    // 刻度取整 - 消抖后的输出四舍五入到 hi_res_step 的整数倍，余量留到下一次，
+    // 适合每格报告值带噪声的有刻度滚轮；两个轴分别取整
+    #[serde(default)]
+    pub snap_to_detent: bool,
+    
+    // 倾斜滚轮重复间隔（毫秒）- 按住倾斜滚轮时连续报告的同方向水平滚动，每个间隔最多放行一次；
+    // 0 表示不限制
+    #[serde(default)]
+    pub hwheel_repeat_ms: u64,
+    
+    // 平滑模式: debounce_only, ema
+    #[serde(default)]
+    pub smoothing_mode: SmoothingMode,
+    
+    // EMA 模式下新值的权重，取值 (0, 1]，越小越平滑
+    #[serde(default = "default_ema_alpha")]
+    pub ema_alpha: f64,
+    
+    // 连续过滤的反向滚动达到此次数后，下一次反向视为有意换向并放行，0 表示不限制
+    #[serde(default)]
+    pub max_filtered_reverses: u32,
+    
+    // 预热事件数：新的滚动连续这么多个事件方向一致后才放行，0 和 1 表示不预热
+    #[serde(default)]
+    pub warmup_events: u32,
+    
+    // 是否启用滚轮故障检测（短时间内大量同类反向被过滤时暂停反向过滤）
+    #[serde(default = "default_fault_detection")]
+    pub fault_detection: bool,
+    
+    // 故障判定阈值：检测窗口内被过滤的同类反向滚动次数
+    #[serde(default = "default_fault_reverse_threshold")]
+    pub fault_reverse_threshold: u32,
+    
+    // 故障检测窗口（毫秒）
+    #[serde(default = "default_fault_window")]
+    pub fault_window_ms: u64,
+    
+    // 判定为故障后暂停反向过滤的时间（毫秒）
+    #[serde(default = "default_fault_cooldown")]
+    pub fault_cooldown_ms: u64,
+    
+    // 是否根据估计的设备回报率归一化平滑和加速的时间参数，不改变滚动距离
+    #[serde(default)]
+    pub normalize_by_polling_rate: bool,
+    
+    // 归一化的参考回报率（Hz）
+    #[serde(default = "default_polling_reference_hz")]
+    pub polling_reference_hz: u32,
+    
+    // 滚动速度倍数，作用于平滑后的高分辨率值
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    
+    // 滚动加速度，0 表示关闭。相邻两次滚动的间隔越短，额外的倍数越大
+    #[serde(default)]
+    pub acceleration: f64,
+    
+    // 加速产生的额外倍数上限
+    #[serde(default = "default_acceleration_max")]
+    pub acceleration_max: f64,
+    
+    // 是否发送标准滚轮事件（REL_WHEEL/REL_HWHEEL），只认高分辨率事件的程序可以关闭以免重复滚动
+    #[serde(default = "default_emit")]
+    pub emit_standard: bool,
+    
+    // 是否发送高分辨率滚轮事件，只认标准事件的老程序可以关闭（需要重启生效）
+    #[serde(default = "default_emit")]
+    pub emit_hi_res: bool,
+    
+    // 高分辨率滚轮事件只按整刻度发送（值总是 hi_res_step 的整数倍，不足一个刻度的余量留到下次），
+    // 与同时发送的标准滚轮事件严格对应，避免自行从高分辨率值换算的程序重复计数
+    #[serde(default)]
+    pub hi_res_whole_detents: bool,
+    
+    // 标准滚轮事件的缩放比例，与 multiplier 不同，只作用于标准事件；小数部分累积到下一次
+    #[serde(default = "default_scale")]
+    pub standard_scale: f64,
+    
+    // 高分辨率滚轮事件的缩放比例，只作用于高分辨率事件；小数部分累积到下一次
+    #[serde(default = "default_scale")]
+    pub hi_res_scale: f64,
+    
+    // 合并窗口（毫秒）- 高回报率设备会把一个刻度拆成多个很小的高分辨率报告，窗口内连续的
+    // 同方向报告先合并成一个值再消抖，凑满一个刻度时立即处理；0 表示不合并
+    #[serde(default)]
+    pub merge_window_ms: u64,
+    
+    // 输出间隔（毫秒）- 滚轮值先累积，每个间隔最多合并发送一次，减少高回报率设备的事件数量；0 表示不合并
+    #[serde(default)]
+    pub output_interval_ms: u64,
+    
+    // 反转垂直滚动方向（自然滚动）
+    #[serde(default)]
+    pub invert_vertical: bool,
+    
+    // 反转水平滚动方向
+    #[serde(default)]
+    pub invert_horizontal: bool,
+    
+    // 反转旋钮方向
+    #[serde(default)]
+    pub invert_dial: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ButtonConfig {
+    // 按键消抖时间（毫秒）- 松开后在此时间内再次按下视为微动开关抖动，0 表示不启用
+    #[serde(default)]
+    pub click_debounce_ms: u64,
+    
+    // 按键映射: 物理按键 -> 虚拟按键，例如 BTN_SIDE = "BTN_MIDDLE"，未列出的按键原样转发
+    #[serde(default)]
+    pub remap: BTreeMap<String, String>,
+    
+    // 水平滚动修饰键（可选），例如 "BTN_SIDE"：按住时垂直滚轮改为水平滚动。
+    // 修饰键本身不转发，按住期间没有滚动就松开时补发一次点击
+    #[serde(default)]
+    pub scroll_modifier: Option<String>,
+}
+
+impl DeviceConfig {
+    /// 解析不转发的事件代码
+    pub fn suppress_event_codes(&self) -> Result<HashSet<EventCode>, String> {
+        self.suppress_codes
+            .iter()
+            .map(|name| parse_event_code(name).ok_or_else(|| format!("device.suppress_codes 中的事件名无效: '{}'，只支持按键（KEY_*/BTN_*）和相对轴（REL_*）", name)))
+            .collect()
+    }
+}
+
+/// 按名称解析按键（EV_KEY，例如 "BTN_TASK"）或相对轴（EV_REL，例如 "REL_HWHEEL"）的事件代码
+pub fn parse_event_code(name: &str) -> Option<EventCode> {
+    if let Ok(key) = name.parse::<EV_KEY>() {
+        return Some(EventCode::EV_KEY(key));
+    }
+    name.parse::<EV_REL>().ok().map(EventCode::EV_REL)
+}
+
+impl ButtonConfig {
+    /// 解析按键映射表
+    pub fn remap_codes(&self) -> Result<HashMap<EV_KEY, EV_KEY>, String> {
+        let mut codes = HashMap::new();
+        for (source, target) in &self.remap {
+            let source_code = source
+                .parse::<EV_KEY>()
+                .map_err(|_| format!("button.remap 中的按键名无效: '{}'", source))?;
+            let target_code = target
+                .parse::<EV_KEY>()
+                .map_err(|_| format!("button.remap 中的按键名无效: '{}'", target))?;
+            codes.insert(source_code, target_code);
+        }
+        Ok(codes)
+    }
+    
+    /// 解析水平滚动修饰键
+    pub fn scroll_modifier_code(&self) -> Result<Option<EV_KEY>, String> {
+        match &self.scroll_modifier {
+            Some(name) => name
+                .parse::<EV_KEY>()
+                .map(Some)
+                .map_err(|_| format!("button.scroll_modifier 中的按键名无效: '{}'", name)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// 滚轮平滑模式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SmoothingMode {
+    // 只做反向抖动过滤
+    #[default]
+    DebounceOnly,
+    // 过滤抖动后再做指数滑动平均，让滚动更有惯性
+    Ema,
+}
+
+/// 日志输出目标
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTarget {
+    // 输出到标准输出，错误输出到标准错误
+    #[default]
+    Stdout,
+    // 全部输出到标准错误
+    Stderr,
+    // 发送到系统日志（journald 会保留日志级别）
+    Syslog,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LoggingConfig {
     // 日志级别: error, warn, info, debug, trace
     #[serde(default = "default_log_level")]
     pub level: String,
+    
+    // 日志输出目标: stdout, stderr, syslog
+    #[serde(default)]
+    pub target: LogTarget,
+    
+    // 是否在每行日志前加时间戳（syslog 自带时间，不受此项影响）
+    #[serde(default = "default_log_timestamps")]
+    pub timestamps: bool,
+    
+    // 日志文件路径（可选），设置后忽略 target，写入该文件
+    #[serde(default)]
+    pub file: Option<String>,
+    
+    // 日志文件超过该大小（MB）时轮转到 <文件名>.1，0 表示不轮转
+    #[serde(default = "default_log_max_size")]
+    pub max_size_mb: u64,
+    
+    // 帮助信息和主要错误消息的语言: auto（按 LANG 等环境变量）, zh, en
+    #[serde(default = "default_log_language")]
+    pub language: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    // 是否启动 Prometheus 指标服务（需要启用 metrics 特性）
+    #[serde(default)]
+    pub enabled: bool,
+    
+    // 监听地址
+    #[serde(default = "default_metrics_address")]
+    pub address: String,
+    
+    // 监听端口
+    #[serde(default = "default_metrics_port")]
+    pub port: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct IpcConfig {
+    // 是否监听控制套接字（需要启用 ipc 特性）
+    #[serde(default)]
+    pub enabled: bool,
+    
+    // Unix 域套接字路径，只有运行程序的用户可以连接
+    #[serde(default = "default_ipc_socket_path")]
+    pub socket_path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DebugConfig {
+    // 录制文件（可选）：把虚拟设备的所有输出事件以 evemu 格式写入该文件，可以用 evemu-play 重放。
+    // 其中的 {name} 替换为原设备名，同时接管多个设备时用来区分
+    #[serde(default)]
+    pub record_path: Option<String>,
 }
 
 fn default_debounce_time() -> u64 {
@@ -60,18 +624,101 @@ fn default_scroll_timeout() -> u64 {
     300
 }
 
+fn default_hi_res_step() -> i32 {
+    120
+}
+
+fn default_jitter_distance_threshold() -> i32 {
+    300
+}
+
+// 与默认值下只有标准滚轮事件的设备相同：300 / 120 向下取整为 2 格
+fn default_dial_jitter_distance_threshold() -> i32 {
+    2
+}
+
+fn default_ema_alpha() -> f64 {
+    0.5
+}
+
+fn default_fault_detection() -> bool {
+    true
+}
+
+fn default_fault_reverse_threshold() -> u32 {
+    6
+}
+
+fn default_fault_window() -> u64 {
+    1000
+}
+
+fn default_fault_cooldown() -> u64 {
+    5000
+}
+
+fn default_polling_reference_hz() -> u32 {
+    125
+}
+
+fn default_emit() -> bool {
+    true
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+fn default_acceleration_max() -> f64 {
+    4.0
+}
+
+fn default_require_rel_axes() -> bool {
+    true
+}
+
+fn default_grab() -> bool {
+    true
+}
+
+fn default_reconnect_interval() -> u64 {
+    1000
+}
+
+fn default_stall_timeout() -> u64 {
+    5000
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            device: DeviceConfig::default(),
-            wheel: WheelConfig::default(),
-            logging: LoggingConfig::default(),
-        }
-    }
+fn default_log_timestamps() -> bool {
+    true
+}
+
+fn default_metrics_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_metrics_port() -> u16 {
+    9477
+}
+
+fn default_ipc_socket_path() -> String {
+    "/run/mouse_smoother.sock".to_string()
+}
+
+fn default_log_max_size() -> u64 {
+    10
+}
+
+fn default_log_language() -> String {
+    "auto".to_string()
 }
 
 impl Default for DeviceConfig {
@@ -79,6 +726,16 @@ impl Default for DeviceConfig {
         DeviceConfig {
             path: None,
             name_filter: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            left_handed: false,
+            include_touchpads: false,
+            require_rel_axes: default_require_rel_axes(),
+            grab: default_grab(),
+            virtual_name: None,
+            reconnect_interval_ms: default_reconnect_interval(),
+            stall_timeout_ms: default_stall_timeout(),
+            suppress_codes: Vec::new(),
         }
     }
 }
@@ -88,7 +745,48 @@ impl Default for WheelConfig {
         WheelConfig {
             debounce_time_ms: default_debounce_time(),
             h_debounce_time_ms: default_debounce_time(),
+            dial_debounce_time_ms: 0,
+            debounce_time_up_ms: None,
+            debounce_time_down_ms: None,
             debounce_timeout_ms: default_scroll_timeout(),
+            continuation_window_ms: 0,
+            hi_res_step: default_hi_res_step(),
+            jitter_distance_threshold: default_jitter_distance_threshold(),
+            h_jitter_distance_threshold: default_jitter_distance_threshold(),
+            dial_jitter_distance_threshold: default_dial_jitter_distance_threshold(),
+            jitter_distance_threshold_up: None,
+            jitter_distance_threshold_down: None,
+            passthrough_threshold: 0,
+            h_passthrough_threshold: 0,
+            dial_passthrough_threshold: 0,
+            deadzone: 0,
+            h_deadzone: 0,
+            dial_deadzone: 0,
+            snap_to_detent: false,
+            hwheel_repeat_ms: 0,
+            smoothing_mode: SmoothingMode::default(),
+            ema_alpha: default_ema_alpha(),
+            max_filtered_reverses: 0,
+            warmup_events: 0,
+            fault_detection: default_fault_detection(),
+            fault_reverse_threshold: default_fault_reverse_threshold(),
+            fault_window_ms: default_fault_window(),
+            fault_cooldown_ms: default_fault_cooldown(),
+            normalize_by_polling_rate: false,
+            polling_reference_hz: default_polling_reference_hz(),
+            multiplier: default_multiplier(),
+            acceleration: 0.0,
+            acceleration_max: default_acceleration_max(),
+            emit_standard: default_emit(),
+            emit_hi_res: default_emit(),
+            hi_res_whole_detents: false,
+            standard_scale: default_scale(),
+            hi_res_scale: default_scale(),
+            merge_window_ms: 0,
+            output_interval_ms: 0,
+            invert_vertical: false,
+            invert_horizontal: false,
+            invert_dial: false,
         }
     }
 }
@@ -97,18 +795,137 @@ impl Default for LoggingConfig {
     fn default() -> Self {
         LoggingConfig {
             level: default_log_level(),
+            target: LogTarget::default(),
+            timestamps: default_log_timestamps(),
+            file: None,
+            max_size_mb: default_log_max_size(),
+            language: default_log_language(),
         }
     }
 }
 
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enabled: false,
+            address: default_metrics_address(),
+            port: default_metrics_port(),
+        }
+    }
+}
+
+impl Default for IpcConfig {
+    fn default() -> Self {
+        IpcConfig {
+            enabled: false,
+            socket_path: default_ipc_socket_path(),
+        }
+    }
+}
+
+// 标准和高分辨率滚轮缩放比例之比超过该值（或低于其倒数）时给出警告
+const MAX_SCALE_RATIO: f64 = 4.0;
+
+// 带注释的配置模板（--create-config --annotated）。serde 不能输出注释，模板手工维护，
+// 增加或修改配置项时需要同步更新
+const ANNOTATED_TEMPLATE: &str = include_str!("config_template.toml");
+
+/// 配置文件格式，按扩展名选择
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// `.json` 为 JSON，`.yaml`/`.yml` 为 YAML，`.toml` 和无扩展名为 TOML，
+    /// 其他扩展名按 TOML 处理并给出警告
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            Some("toml") | None => ConfigFormat::Toml,
+            Some(ext) => {
+                log_warn!("无法识别配置文件扩展名 '.{}'，按 TOML 格式处理", ext);
+                ConfigFormat::Toml
+            }
+        }
+    }
+    
+    fn parse(self, contents: &str) -> Result<Config, Error> {
+        let invalid = |e: &dyn std::fmt::Display| Error::ConfigInvalid(e.to_string());
+        match self {
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|e| invalid(&e)),
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| invalid(&e)),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| invalid(&e)),
+            #[allow(unreachable_patterns)]
+            format => Err(Error::ConfigInvalid(format.missing_feature())),
+        }
+    }
+    
+    fn serialize(self, config: &Config) -> Result<String, Error> {
+        let invalid = |e: &dyn std::fmt::Display| Error::ConfigInvalid(e.to_string());
+        match self {
+            ConfigFormat::Toml => toml::to_string_pretty(config).map_err(|e| invalid(&e)),
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => serde_json::to_string_pretty(config).map_err(|e| invalid(&e)),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml::to_string(config).map_err(|e| invalid(&e)),
+            #[allow(unreachable_patterns)]
+            format => Err(Error::ConfigInvalid(format.missing_feature())),
+        }
+    }
+    
+    /// 未启用对应特性时的错误信息
+    fn missing_feature(self) -> String {
+        let feature = match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Toml => "toml",
+        };
+        format!("读取 {:?} 格式的配置文件需要启用 {} 特性", self, feature)
+    }
+}
+
 impl Config {
+    /// 未指定 `--config` 时使用的配置文件路径
+    ///
+    /// 依次检查：`MOUSE_SMOOTHER_CONFIG` 环境变量；`prefer_user` 为 true（非 root 运行）
+    /// 且 `$XDG_CONFIG_HOME/mouse_smoother/config.toml`（未设置时为 `~/.config/...`）
+    /// 存在时使用它；否则为 `/etc/mouse_smoother.toml`。
+    pub fn default_path(prefer_user: bool) -> String {
+        if let Ok(path) = std::env::var("MOUSE_SMOOTHER_CONFIG") {
+            if !path.is_empty() {
+                return path;
+            }
+        }
+        
+        if prefer_user {
+            let config_home = match std::env::var("XDG_CONFIG_HOME") {
+                Ok(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+                _ => std::env::var("HOME").ok().map(|home| Path::new(&home).join(".config")),
+            };
+            if let Some(config_home) = config_home {
+                let path = config_home.join("mouse_smoother").join("config.toml");
+                if path.exists() {
+                    return path.to_string_lossy().into_owned();
+                }
+            }
+        }
+        
+        String::from("/etc/mouse_smoother.toml")
+    }
+    
     /// 从指定路径加载配置文件
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let path = path.as_ref();
         
         // 检查文件是否存在
         if !path.exists() {
-            println!("配置文件 {} 不存在，使用默认配置", path.display());
+            log_info!("配置文件 {} 不存在，使用默认配置", path.display());
             return Ok(Config::default());
         }
         
@@ -117,43 +934,419 @@ impl Config {
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
         
-        // 解析TOML格式
-        let config: Config = toml::from_str(&contents)?;
-        println!("已加载配置文件: {}", path.display());
+        // 按扩展名选择格式解析
+        let config = ConfigFormat::from_path(path).parse(&contents)?;
+        
+        config.validate().map_err(|e| Error::ConfigInvalid(format!("配置错误: {}", e)))?;
+        log_info!("已加载配置文件: {}", path.display());
         
         Ok(config)
     }
     
+    /// 检查配置值是否合理，返回第一个有问题的字段
+    ///
+    /// 消抖时间为 0 是有意义的（关闭对应滚轮的消抖），不在此拒绝；
+    /// 消抖超时也允许小于消抖时间，此时超时之后的反向滚动按距离判断是否为抖动。
+    pub fn validate(&self) -> Result<(), String> {
+        let wheel = &self.wheel;
+        
+        if wheel.debounce_timeout_ms == 0 {
+            return Err("wheel.debounce_timeout_ms 必须大于 0".to_string());
+        }
+        
+        // 高分辨率步长用作除数，必须为正数
+        if wheel.hi_res_step <= 0 {
+            return Err(format!("wheel.hi_res_step 必须大于 0，当前值为 {}", wheel.hi_res_step));
+        }
+        
+        if wheel.passthrough_threshold < 0 || wheel.h_passthrough_threshold < 0 || wheel.dial_passthrough_threshold < 0 {
+            return Err("wheel.passthrough_threshold、wheel.h_passthrough_threshold 和 wheel.dial_passthrough_threshold 不能小于 0".to_string());
+        }
+        
+        if wheel.deadzone < 0 || wheel.h_deadzone < 0 || wheel.dial_deadzone < 0 {
+            return Err("wheel.deadzone、wheel.h_deadzone 和 wheel.dial_deadzone 不能小于 0".to_string());
+        }
+        
+        if !(wheel.ema_alpha > 0.0 && wheel.ema_alpha <= 1.0) {
+            return Err(format!("wheel.ema_alpha 必须在 (0, 1] 范围内，当前值为 {}", wheel.ema_alpha));
+        }
+        
+        if !(wheel.multiplier.is_finite() && wheel.multiplier > 0.0) {
+            return Err(format!("wheel.multiplier 必须大于 0，当前值为 {}", wheel.multiplier));
+        }
+        
+        if !(wheel.acceleration.is_finite() && wheel.acceleration >= 0.0) {
+            return Err(format!("wheel.acceleration 不能小于 0，当前值为 {}", wheel.acceleration));
+        }
+        
+        if !(wheel.acceleration_max.is_finite() && wheel.acceleration_max >= 1.0) {
+            return Err(format!("wheel.acceleration_max 不能小于 1，当前值为 {}", wheel.acceleration_max));
+        }
+        
+        if !wheel.emit_standard && !wheel.emit_hi_res {
+            return Err("wheel.emit_standard 和 wheel.emit_hi_res 不能同时关闭".to_string());
+        }
+        
+        for (name, scale) in [("standard_scale", wheel.standard_scale), ("hi_res_scale", wheel.hi_res_scale)] {
+            if !(scale.is_finite() && scale > 0.0) {
+                return Err(format!("wheel.{} 必须大于 0，当前值为 {}", name, scale));
+            }
+        }
+        
+        if wheel.fault_detection {
+            if wheel.fault_reverse_threshold == 0 {
+                return Err("wheel.fault_reverse_threshold 必须大于 0".to_string());
+            }
+            if wheel.fault_window_ms == 0 {
+                return Err("wheel.fault_window_ms 必须大于 0".to_string());
+            }
+        }
+        
+        if wheel.normalize_by_polling_rate && wheel.polling_reference_hz == 0 {
+            return Err("wheel.polling_reference_hz 必须大于 0".to_string());
+        }
+        
+        // uinput 设备名最长 80 字节（含结尾的 0）
+        if let Some(name) = &self.device.virtual_name {
+            if name.trim().is_empty() || name.len() >= 80 {
+                return Err(format!("device.virtual_name 不能为空且不能超过 79 字节: '{}'", name));
+            }
+        }
+        
+        if let Some(NameFilter::Regex { regex }) = &self.device.name_filter {
+            regex::Regex::new(regex)
+                .map_err(|e| format!("device.name_filter 中的正则表达式无效: {}", e))?;
+        }
+        
+        for profile in &self.profiles {
+            if profile.window_class.is_empty() {
+                return Err("profiles 中的 window_class 不能为空".to_string());
+            }
+            if profile.debounce_timeout_ms == Some(0) {
+                return Err(format!("应用配置 '{}' 的 debounce_timeout_ms 必须大于 0", profile.window_class));
+            }
+            if let Some(multiplier) = profile.multiplier {
+                if !(multiplier.is_finite() && multiplier > 0.0) {
+                    return Err(format!("应用配置 '{}' 的 multiplier 必须大于 0，当前值为 {}", profile.window_class, multiplier));
+                }
+            }
+        }
+        
+        for profile in &self.device_profile {
+            if profile.matcher.is_empty() {
+                return Err("device_profile 中的 match 不能为空".to_string());
+            }
+            if profile.debounce_timeout_ms == Some(0) {
+                return Err(format!("设备配置 '{}' 的 debounce_timeout_ms 必须大于 0", profile.matcher));
+            }
+            if let Some(step) = profile.hi_res_step {
+                if step <= 0 {
+                    return Err(format!("设备配置 '{}' 的 hi_res_step 必须大于 0，当前值为 {}", profile.matcher, step));
+                }
+            }
+            if let Some(multiplier) = profile.multiplier {
+                if !(multiplier.is_finite() && multiplier > 0.0) {
+                    return Err(format!("设备配置 '{}' 的 multiplier 必须大于 0，当前值为 {}", profile.matcher, multiplier));
+                }
+            }
+        }
+        
+        self.device.suppress_event_codes()?;
+        self.button.remap_codes()?;
+        let scroll_modifier = self.button.scroll_modifier_code()?;
+        
+        for binding in self.wheel_bindings()? {
+            // 水平滚动修饰键会把垂直滚动改成水平滚动，同一个键不能再用于绑定
+            if let Some(modifier) = binding.modifier.filter(|&key| Some(key) == scroll_modifier) {
+                return Err(format!("wheel_binding 的修饰键 {:?} 已用作 button.scroll_modifier", modifier));
+            }
+        }
+        
+        if LogLevel::from_str(&self.logging.level).is_none() {
+            return Err(format!(
+                "logging.level 无效: '{}'，可选值为 error, warn, info, debug, trace",
+                self.logging.level
+            ));
+        }
+        
+        if Language::from_str(&self.logging.language).is_none() {
+            return Err(format!(
+                "logging.language 无效: '{}'，可选值为 auto, zh, en",
+                self.logging.language
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    /// 解析所有滚轮按键绑定
+    pub fn wheel_bindings(&self) -> Result<Vec<WheelBinding>, String> {
+        self.wheel_binding.iter().map(WheelBindingConfig::codes).collect()
+    }
+    
+    /// 应用命令行覆盖参数并重新检查配置
+    ///
+    /// 覆盖参数会保存在配置中，SIGHUP 重新加载配置文件后再次应用。
+    pub fn apply_overrides(&mut self, overrides: ConfigOverrides) -> Result<(), String> {
+        if let Some(value) = overrides.debounce_time_ms {
+            self.wheel.debounce_time_ms = value;
+        }
+        if let Some(value) = overrides.h_debounce_time_ms {
+            self.wheel.h_debounce_time_ms = value;
+        }
+        if let Some(value) = overrides.debounce_timeout_ms {
+            self.wheel.debounce_timeout_ms = value;
+        }
+        self.overrides = overrides;
+        self.validate()
+    }
+    
+    /// 应用匹配设备的设备配置，得到该设备的基础配置
+    ///
+    /// 没有匹配的设备配置时返回原配置。命令行覆盖参数优先于设备配置，之后再次应用。
+    pub fn for_device(&self, name: &str, vendor: u16, product: u16) -> Config {
+        let mut config = self.clone();
+        let profile = match self.device_profile.iter().find(|profile| profile.matches(name, vendor, product)) {
+            Some(profile) => profile,
+            None => return config,
+        };
+        log_info!("设备 {} 使用设备配置 '{}'", name, profile.matcher);
+        
+        let wheel = &mut config.wheel;
+        if let Some(value) = profile.debounce_time_ms {
+            wheel.debounce_time_ms = value;
+        }
+        if let Some(value) = profile.h_debounce_time_ms {
+            wheel.h_debounce_time_ms = value;
+        }
+        if let Some(value) = profile.debounce_timeout_ms {
+            wheel.debounce_timeout_ms = value;
+        }
+        if let Some(value) = profile.jitter_distance_threshold {
+            wheel.jitter_distance_threshold = value;
+        }
+        if let Some(value) = profile.h_jitter_distance_threshold {
+            wheel.h_jitter_distance_threshold = value;
+        }
+        if let Some(value) = profile.hi_res_step {
+            wheel.hi_res_step = value;
+        }
+        if let Some(value) = profile.multiplier {
+            wheel.multiplier = value;
+        }
+        if let Some(value) = profile.invert_vertical {
+            wheel.invert_vertical = value;
+        }
+        if let Some(value) = profile.invert_horizontal {
+            wheel.invert_horizontal = value;
+        }
+        // 设备配置和覆盖参数都已检查过，这里不会失败
+        let _ = config.apply_overrides(self.overrides.clone());
+        config
+    }
+    
+    /// 查找匹配焦点窗口的应用配置
+    pub fn find_profile(&self, window_classes: &[String]) -> Option<&ProfileConfig> {
+        self.profiles.iter().find(|profile| profile.matches(window_classes))
+    }
+    
+    /// 在基础配置上应用应用配置，得到实际使用的配置
+    ///
+    /// 命令行覆盖参数优先于应用配置，之后再次应用。
+    pub fn with_profile(&self, profile: Option<&ProfileConfig>) -> Config {
+        let mut config = self.clone();
+        if let Some(profile) = profile {
+            if let Some(value) = profile.debounce_time_ms {
+                config.wheel.debounce_time_ms = value;
+            }
+            if let Some(value) = profile.h_debounce_time_ms {
+                config.wheel.h_debounce_time_ms = value;
+            }
+            if let Some(value) = profile.debounce_timeout_ms {
+                config.wheel.debounce_timeout_ms = value;
+            }
+            if let Some(value) = profile.multiplier {
+                config.wheel.multiplier = value;
+            }
+            // 应用配置和覆盖参数都已检查过，这里不会失败
+            let _ = config.apply_overrides(self.overrides.clone());
+        }
+        config
+    }
+    
+    /// 标准和高分辨率滚轮事件的缩放比例相差过大时给出警告
+    ///
+    /// 同时读取两种事件的程序（例如按高分辨率值滚动、按标准事件翻页）会看到不一致的滚动距离。
+    pub fn warn_scale_divergence(&self) {
+        let wheel = &self.wheel;
+        if !(wheel.emit_standard && wheel.emit_hi_res) {
+            return;
+        }
+        let ratio = wheel.hi_res_scale / wheel.standard_scale;
+        if !(1.0 / MAX_SCALE_RATIO..=MAX_SCALE_RATIO).contains(&ratio) {
+            log_warn!(
+                "wheel.hi_res_scale ({}) 与 wheel.standard_scale ({}) 相差超过 {} 倍，同时读取两种滚轮事件的程序滚动距离会不一致",
+                wheel.hi_res_scale, wheel.standard_scale, MAX_SCALE_RATIO
+            );
+        }
+    }
+    
     /// 获取垂直滚轮消抖时间
     pub fn get_debounce_time(&self) -> Duration {
         Duration::from_millis(self.wheel.debounce_time_ms)
     }
     
+    /// 获取垂直滚轮向上滚动时的消抖时间，未单独设置时与 `get_debounce_time` 相同
+    pub fn get_debounce_time_up(&self) -> Duration {
+        Duration::from_millis(self.wheel.debounce_time_up_ms.unwrap_or(self.wheel.debounce_time_ms))
+    }
+    
+    /// 获取垂直滚轮向下滚动时的消抖时间
+    pub fn get_debounce_time_down(&self) -> Duration {
+        Duration::from_millis(self.wheel.debounce_time_down_ms.unwrap_or(self.wheel.debounce_time_ms))
+    }
+    
+    /// 垂直滚轮是否启用消抖（任一方向的消抖时间不为 0）
+    pub fn vertical_debounce_enabled(&self) -> bool {
+        !self.get_debounce_time_up().is_zero() || !self.get_debounce_time_down().is_zero()
+    }
+    
     /// 获取水平滚轮消抖时间
     pub fn get_h_debounce_time(&self) -> Duration {
         Duration::from_millis(self.wheel.h_debounce_time_ms)
     }
     
+    /// 获取旋钮消抖时间
+    pub fn get_dial_debounce_time(&self) -> Duration {
+        Duration::from_millis(self.wheel.dial_debounce_time_ms)
+    }
+    
     /// 获取消抖超时时间
     pub fn get_debounce_timeout(&self) -> Duration {
         Duration::from_millis(self.wheel.debounce_timeout_ms)
     }
     
+    /// 获取连续滚动窗口
+    pub fn get_continuation_window(&self) -> Duration {
+        Duration::from_millis(self.wheel.continuation_window_ms)
+    }
+    
+    /// 获取倾斜滚轮的重复间隔
+    pub fn get_hwheel_repeat_interval(&self) -> Duration {
+        Duration::from_millis(self.wheel.hwheel_repeat_ms)
+    }
+    
+    /// 获取滚轮输入的合并窗口
+    pub fn get_merge_window(&self) -> Duration {
+        Duration::from_millis(self.wheel.merge_window_ms)
+    }
+    
+    /// 获取滚轮输出的合并间隔
+    pub fn get_output_interval(&self) -> Duration {
+        Duration::from_millis(self.wheel.output_interval_ms)
+    }
+    
+    /// 获取按键消抖时间
+    pub fn get_click_debounce_time(&self) -> Duration {
+        Duration::from_millis(self.button.click_debounce_ms)
+    }
+    
+    /// 获取故障检测窗口
+    pub fn get_fault_window(&self) -> Duration {
+        Duration::from_millis(self.wheel.fault_window_ms)
+    }
+    
+    /// 获取故障后暂停反向过滤的时间
+    pub fn get_fault_cooldown(&self) -> Duration {
+        Duration::from_millis(self.wheel.fault_cooldown_ms)
+    }
+    
+    /// 获取设备断开后的重连间隔
+    pub fn get_reconnect_interval(&self) -> Duration {
+        Duration::from_millis(self.device.reconnect_interval_ms)
+    }
+    
+    /// 为 `device_name` 对应的设备生成虚拟设备名称
+    pub fn virtual_device_name(&self, device_name: &str) -> String {
+        match &self.device.virtual_name {
+            Some(name) => name.replace("{name}", device_name),
+            None => format!("Virtual {}", device_name),
+        }
+    }
+    
+    /// 设备的录制文件路径，未设置 debug.record_path 时为 None
+    pub fn record_path(&self, device_name: &str) -> Option<String> {
+        self.debug
+            .record_path
+            .as_ref()
+            .map(|path| path.replace("{name}", &device_name.replace('/', "_")))
+    }
+    
+    /// 获取设备失效检测的超时时间
+    pub fn get_stall_timeout(&self) -> Duration {
+        Duration::from_millis(self.device.stall_timeout_ms)
+    }
+    
     /// 保存配置到文件
-    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
-        let toml_string = toml::to_string_pretty(self)?;
-        std::fs::write(path, toml_string)?;
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let contents = ConfigFormat::from_path(path).serialize(self)?;
+        std::fs::write(path, contents)?;
         Ok(())
     }
     
     /// 创建默认配置文件（如果不存在）
-    pub fn create_default<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn create_default<P: AsRef<Path>>(path: P) -> Result<(), Error> {
         let path = path.as_ref();
         if !path.exists() {
             let config = Config::default();
             config.save(path)?;
-            println!("已创建默认配置文件: {}", path.display());
+            log_info!("已创建默认配置文件: {}", path.display());
         }
         Ok(())
     }
+    
+    /// 创建带注释的配置文件（如果不存在），内容为手工维护的 TOML 模板
+    pub fn create_annotated<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if ConfigFormat::from_path(path) != ConfigFormat::Toml {
+            return Err(Error::InvalidArgument(format!("带注释的配置模板只有 TOML 格式，不能写入 {}", path.display())));
+        }
+        if !path.exists() {
+            std::fs::write(path, ANNOTATED_TEMPLATE)?;
+            log_info!("已创建带注释的配置文件: {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppress_codes_accept_keys_and_relative_axes() {
+        let device = DeviceConfig {
+            suppress_codes: vec!["BTN_TASK".to_string(), "KEY_VOLUMEUP".to_string(), "REL_HWHEEL".to_string()],
+            ..DeviceConfig::default()
+        };
+        let codes = device.suppress_event_codes().unwrap();
+        assert_eq!(codes, HashSet::from([
+            EventCode::EV_KEY(EV_KEY::BTN_TASK),
+            EventCode::EV_KEY(EV_KEY::KEY_VOLUMEUP),
+            EventCode::EV_REL(EV_REL::REL_HWHEEL),
+        ]));
+    }
+
+    #[test]
+    fn suppress_codes_reject_unknown_names() {
+        for name in ["BTN_NOPE", "MSC_SCAN", "btn_task"] {
+            let device = DeviceConfig {
+                suppress_codes: vec![name.to_string()],
+                ..DeviceConfig::default()
+            };
+            let e = device.suppress_event_codes().unwrap_err();
+            assert!(e.contains(name), "{}", e);
+        }
+    }
 }
@@ -0,0 +1,49 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// 脱离终端转入后台运行
+///
+/// 必须在创建任何线程之前调用。标准输入输出重定向到 /dev/null；保留当前工作目录，
+/// 使相对路径的配置文件在 SIGHUP 重新加载时仍然有效。
+pub fn daemonize() -> io::Result<()> {
+    if unsafe { libc::daemon(1, 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// PID 文件，离开作用域时删除
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// 写入当前进程的 PID
+    ///
+    /// 文件已存在且记录的进程仍在运行时返回错误，避免重复启动；
+    /// 进程已经不存在的残留文件直接覆盖。
+    pub fn create(path: &str) -> io::Result<Self> {
+        if let Ok(contents) = fs::read_to_string(path) {
+            if let Ok(pid) = contents.trim().parse::<libc::pid_t>() {
+                if pid > 0 && unsafe { libc::kill(pid, 0) } == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("PID 文件 {} 对应的进程 {} 仍在运行", path, pid),
+                    ));
+                }
+            }
+        }
+
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        Ok(PidFile {
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
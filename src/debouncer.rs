@@ -1,28 +1,688 @@
+use evdev_rs::enums::EV_KEY;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::time::{Duration, Instant};
-use crate::{log_info, log_debug};
+use crate::clock::{Clock, RealClock};
+use crate::config::{Config, SmoothingMode};
+use crate::{log_info, log_debug, log_trace, log_warn};
 
-pub struct WheelDebouncer {
-    debounce_time: Duration,
+/// 一次 `smooth` 调用的判定结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decision {
+    // 超过直通阈值，绕过消抖
+    PassThrough,
+    // 新的滚动开始
+    NewScroll,
+    // 与上次方向相同
+    Pass,
+    // 故障保护期内放行的反向
+    FaultBypass,
+    // 消抖持续超过超时，放行反向
+    DebounceTimeout,
+    // 超时后距离足够大的反向，视为有意换向
+    Reverse,
+    // 连续过滤的反向达到上限，视为有意换向
+    ReverseLimit,
+    // 消抖超时内的反向，视为抖动过滤
+    FilterJitter,
+    // 超时后距离过小的反向，视为抖动过滤
+    FilterDistance,
+    // 新的滚动在预热中确定了方向，放行缓冲的滚动
+    Warmup,
+    // 在死区内或预热中，累积到下一次
+    Deferred,
+    // 零值事件
+    Zero,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Decision::PassThrough => "passthrough",
+            Decision::NewScroll => "new_scroll",
+            Decision::Pass => "pass",
+            Decision::FaultBypass => "fault_bypass",
+            Decision::DebounceTimeout => "debounce_timeout",
+            Decision::Reverse => "reverse",
+            Decision::ReverseLimit => "reverse_limit",
+            Decision::FilterJitter => "filter_jitter",
+            Decision::FilterDistance => "filter_distance",
+            Decision::Warmup => "warmup",
+            Decision::Deferred => "deferred",
+            Decision::Zero => "zero",
+        };
+        f.write_str(name)
+    }
+}
+
+/// `smooth` 的完整判定记录，trace 级别下每次调用输出一行 `key=value` 格式的日志，
+/// 便于在用户提交的日志中按字段筛选
+struct DecisionTrace {
+    input: i32,
+    last_direction: i32,
+    since_last: Duration,
+    new_scroll: bool,
+    debouncing: bool,
+    fault_bypass: bool,
+    decision: Decision,
+    output: i32,
+}
+
+impl fmt::Display for DecisionTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "wheel_decision input={} direction={} last_direction={} since_last_us={} new_scroll={} debouncing={} fault_bypass={} decision={} output={}",
+            self.input,
+            self.input.signum(),
+            self.last_direction,
+            self.since_last.as_micros(),
+            self.new_scroll,
+            self.debouncing,
+            self.fault_bypass,
+            self.decision,
+            self.output
+        )
+    }
+}
+
+/// 滚轮故障检测参数
+///
+/// 编码器损坏时滚轮可能"卡"在一个方向上，反向过滤反而会吞掉用户真正的操作。
+/// 如果在 `window` 内被过滤的同类反向滚动达到 `threshold` 次，就认为设备可能
+/// 出现故障，在 `cooldown` 时间内暂停反向过滤，让用户重新获得控制。
+#[derive(Debug, Clone, Copy)]
+pub struct FaultDetection {
+    pub enabled: bool,
+    pub threshold: u32,
+    pub window: Duration,
+    pub cooldown: Duration,
+}
+
+/// 按滚动方向区分的参数，`up` 用于正值方向（向上、向右），`down` 用于负值方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerDirection<T> {
+    pub up: T,
+    pub down: T,
+}
+
+impl<T: Copy + Ord> PerDirection<T> {
+    /// 两个方向使用相同的值
+    pub fn both(value: T) -> Self {
+        PerDirection { up: value, down: value }
+    }
+
+    /// 取 `direction` 方向的值，没有方向时取较大的一个
+    pub fn get(&self, direction: i32) -> T {
+        match direction.signum() {
+            1 => self.up,
+            -1 => self.down,
+            _ => self.up.max(self.down),
+        }
+    }
+
+    fn map(self, f: impl Fn(T) -> T) -> Self {
+        PerDirection { up: f(self.up), down: f(self.down) }
+    }
+}
+
+/// 滚轮消抖参数
+#[derive(Debug, Clone, Copy)]
+pub struct DebounceParams {
+    // 消抖时间和抖动距离阈值按正在进行的滚动方向选用
+    pub debounce_time: PerDirection<Duration>,
+    pub debounce_timeout: Duration,
+    // 间隔超过消抖时间但不超过该值时仍视为同一次滚动的延续
+    pub continuation_window: Duration,
+    // 超过消抖超时的反向滚动，距离不超过该值（高分辨率单位）时仍视为抖动
+    pub jitter_distance_threshold: PerDirection<i32>,
+    // 单次滚动值的绝对值超过该值时绕过消抖，0 表示不启用
+    pub passthrough_threshold: i32,
+    // 绝对值小于该值的输出先累积，达到该值后再发送，0 表示不启用
+    pub deadzone: i32,
+    // 输出取整到该值（一个刻度）的整数倍，余量留到下一次，0 表示不启用
+    pub snap_step: i32,
+    // 连续过滤的反向达到该次数后放行下一次反向，0 表示不限制
+    pub max_filtered_reverses: u32,
+    // 新的滚动连续这么多个事件方向一致后才放行，0 和 1 表示不预热
+    pub warmup_events: u32,
+    pub fault_detection: FaultDetection,
+    pub smoothing_mode: SmoothingMode,
+    // EMA 模式下新值的权重，取值 (0, 1]
+    pub ema_alpha: f64,
+}
+
+impl DebounceParams {
+    /// 根据配置生成垂直（或水平）滚轮的消抖参数
+    pub fn from_config(config: &Config, horizontal: bool) -> Self {
+        // 只有垂直滚轮可以按方向设置消抖时间和抖动距离
+        let (debounce_time, jitter_distance_threshold, passthrough_threshold, deadzone) = if horizontal {
+            (
+                PerDirection::both(config.get_h_debounce_time()),
+                PerDirection::both(config.wheel.h_jitter_distance_threshold),
+                config.wheel.h_passthrough_threshold,
+                config.wheel.h_deadzone,
+            )
+        } else {
+            let jitter = config.wheel.jitter_distance_threshold;
+            (
+                PerDirection {
+                    up: config.get_debounce_time_up(),
+                    down: config.get_debounce_time_down(),
+                },
+                PerDirection {
+                    up: config.wheel.jitter_distance_threshold_up.unwrap_or(jitter),
+                    down: config.wheel.jitter_distance_threshold_down.unwrap_or(jitter),
+                },
+                config.wheel.passthrough_threshold,
+                config.wheel.deadzone,
+            )
+        };
+
+        DebounceParams {
+            debounce_time,
+            debounce_timeout: config.get_debounce_timeout(),
+            continuation_window: config.get_continuation_window(),
+            jitter_distance_threshold,
+            passthrough_threshold,
+            deadzone,
+            snap_step: if config.wheel.snap_to_detent { config.wheel.hi_res_step } else { 0 },
+            max_filtered_reverses: config.wheel.max_filtered_reverses,
+            warmup_events: config.wheel.warmup_events,
+            fault_detection: FaultDetection {
+                enabled: config.wheel.fault_detection,
+                threshold: config.wheel.fault_reverse_threshold,
+                window: config.get_fault_window(),
+                cooldown: config.get_fault_cooldown(),
+            },
+            smoothing_mode: config.wheel.smoothing_mode,
+            ema_alpha: config.wheel.ema_alpha,
+        }
+    }
+
+    /// 根据配置生成旋钮（REL_DIAL）的消抖参数
+    ///
+    /// 消抖时间和距离类阈值使用旋钮自己的设置（距离以格数为单位），其他参数与滚轮相同。
+    pub fn for_dial(config: &Config) -> Self {
+        DebounceParams {
+            debounce_time: PerDirection::both(config.get_dial_debounce_time()),
+            jitter_distance_threshold: PerDirection::both(config.wheel.dial_jitter_distance_threshold),
+            passthrough_threshold: config.wheel.dial_passthrough_threshold,
+            deadzone: config.wheel.dial_deadzone,
+            snap_step: 0,
+            ..DebounceParams::from_config(config, true)
+        }
+    }
+
+    /// 把距离类阈值从高分辨率单位换算为刻度数，用于只有标准滚轮事件的设备
+    ///
+    /// 换算后的判断与高分辨率设备上"刻度数 × `hi_res_step`"的判断一致：
+    /// 抖动距离和直通阈值向下取整，死区向上取整。刻度数本来就是整数，不需要取整。
+    pub fn in_detents(self, hi_res_step: i32) -> Self {
+        let step = hi_res_step.max(1);
+        DebounceParams {
+            jitter_distance_threshold: self.jitter_distance_threshold.map(|threshold| threshold / step),
+            passthrough_threshold: self.passthrough_threshold / step,
+            deadzone: self.deadzone.saturating_add(step - 1) / step,
+            snap_step: 0,
+            ..self
+        }
+    }
+}
+
+/// 滚轮消抖器，时间来自 `C`（默认为系统时钟）
+pub struct WheelDebouncer<C: Clock = RealClock> {
+    clock: C,
+    debounce_time: PerDirection<Duration>,
     debounce_timeout: Duration,
+    continuation_window: Duration,
+    jitter_distance_threshold: PerDirection<i32>,
+    passthrough_threshold: i32,
+    deadzone: i32,
+    // 死区内尚未发送的累积值，以及最近一次输出是否被累积
+    deadzone_carry: i32,
+    deferred: bool,
+    snap_step: i32,
+    // 取整到刻度后尚未发送的余量
+    snap_carry: i32,
+    max_filtered_reverses: u32,
+    // 当前连续被过滤的反向次数
+    filtered_reverses: u32,
+    warmup_events: u32,
+    // 预热中缓冲的滚动值，以及最晚放行的时间
+    warmup: Vec<i32>,
+    warmup_until: Option<Instant>,
+    smoothing_mode: SmoothingMode,
+    ema_alpha: f64,
+    // 每个事件相当于参考回报率下的几次报告，回报率归一化时由设备线程更新
+    report_periods: f64,
+    // EMA 模式的滑动平均值和尚未发送的小数部分
+    ema_value: f64,
+    ema_carry: f64,
     last_direction: i32,
     last_scroll_time: Instant,
+    // 最近一次滚动与上一次滚动的间隔
+    last_interval: Duration,
     is_scrolling: bool,
     debounce_start_time: Option<Instant>,
+    fault_detection: FaultDetection,
+    // 最近被过滤的反向滚动时间（仅记录同一方向）
+    suppressed_reverses: VecDeque<Instant>,
+    suppressed_direction: i32,
+    // 故障保护期结束时间，期间不做反向过滤
+    fault_until: Option<Instant>,
 }
 
 impl WheelDebouncer {
-    pub fn new(debounce_time: Duration, debounce_timeout: Duration) -> Self {
+    pub fn new(params: DebounceParams) -> Self {
+        WheelDebouncer::with_clock(params, RealClock)
+    }
+}
+
+impl<C: Clock> WheelDebouncer<C> {
+    /// 使用指定的时钟创建消抖器
+    pub fn with_clock(params: DebounceParams, clock: C) -> Self {
+        let now = clock.now();
         WheelDebouncer {
-            debounce_time,
-            debounce_timeout,
+            clock,
+            debounce_time: params.debounce_time,
+            debounce_timeout: params.debounce_timeout,
+            continuation_window: params.continuation_window,
+            jitter_distance_threshold: params.jitter_distance_threshold,
+            passthrough_threshold: params.passthrough_threshold,
+            deadzone: params.deadzone,
+            deadzone_carry: 0,
+            deferred: false,
+            snap_step: params.snap_step,
+            snap_carry: 0,
+            max_filtered_reverses: params.max_filtered_reverses,
+            filtered_reverses: 0,
+            warmup_events: params.warmup_events,
+            warmup: Vec::new(),
+            warmup_until: None,
+            smoothing_mode: params.smoothing_mode,
+            ema_alpha: params.ema_alpha,
+            report_periods: 1.0,
+            ema_value: 0.0,
+            ema_carry: 0.0,
             last_direction: 0,
-            last_scroll_time: Instant::now(),
+            last_scroll_time: now,
+            last_interval: Duration::MAX,
             is_scrolling: false,
             debounce_start_time: None,
+            fault_detection: params.fault_detection,
+            suppressed_reverses: VecDeque::new(),
+            suppressed_direction: 0,
+            fault_until: None,
+        }
+    }
+
+    /// 运行时更新消抖参数，保留当前的滚动状态
+    pub fn update_params(&mut self, params: DebounceParams) {
+        self.debounce_time = params.debounce_time;
+        self.debounce_timeout = params.debounce_timeout;
+        self.continuation_window = params.continuation_window;
+        self.jitter_distance_threshold = params.jitter_distance_threshold;
+        self.passthrough_threshold = params.passthrough_threshold;
+        self.deadzone = params.deadzone;
+        self.snap_step = params.snap_step;
+        self.max_filtered_reverses = params.max_filtered_reverses;
+        self.warmup_events = params.warmup_events;
+        self.fault_detection = params.fault_detection;
+        self.smoothing_mode = params.smoothing_mode;
+        self.ema_alpha = params.ema_alpha;
+    }
+
+    /// 是否处于故障保护期（暂停反向过滤）
+    fn in_fault_bypass(&mut self, now: Instant) -> bool {
+        match self.fault_until {
+            Some(until) if now < until => true,
+            Some(_) => {
+                log_info!("故障保护期结束，恢复反向过滤");
+                self.fault_until = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// 记录一次被过滤的反向滚动，必要时进入故障保护期
+    fn record_suppressed_reverse(&mut self, direction: i32, now: Instant) {
+        if !self.fault_detection.enabled {
+            return;
+        }
+
+        // 只统计同一类反向，方向变化时重新计数
+        if direction != self.suppressed_direction {
+            self.suppressed_reverses.clear();
+            self.suppressed_direction = direction;
+        }
+
+        self.suppressed_reverses.push_back(now);
+        while let Some(&first) = self.suppressed_reverses.front() {
+            if now.duration_since(first) > self.fault_detection.window {
+                self.suppressed_reverses.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.suppressed_reverses.len() as u32 >= self.fault_detection.threshold.max(1) {
+            log_warn!(
+                "{:?} 内过滤了 {} 次方向为 {} 的反向滚动，滚轮可能出现故障，暂停反向过滤 {:?}",
+                self.fault_detection.window,
+                self.suppressed_reverses.len(),
+                direction,
+                self.fault_detection.cooldown
+            );
+            self.fault_until = Some(now + self.fault_detection.cooldown);
+            self.suppressed_reverses.clear();
+        }
+    }
+
+    /// 对一个高分辨率滚动值做消抖和平滑，返回应当发送的值（0 表示过滤）
+    pub fn smooth(&mut self, value: i32) -> i32 {
+        let now = self.clock.now();
+        self.last_interval = now.duration_since(self.last_scroll_time);
+        self.deferred = false;
+        let input = value;
+        let last_direction = self.last_direction;
+        let new_scroll = self.is_new_scroll(self.last_interval);
+
+        // 用力快速滚动一定是有意的，不做过滤和平均
+        let (output, decision) = if self.passthrough_threshold > 0 && value.saturating_abs() > self.passthrough_threshold {
+            log_debug!("滚动值 {} 超过直通阈值 {}，直接发送", value, self.passthrough_threshold);
+            self.pass_through(value, now);
+            (value, Decision::PassThrough)
+        } else if let Some(value) = self.warm_up(value, now, new_scroll) {
+            (self.average(value, true), Decision::Warmup)
+        } else {
+            let (value, decision) = self.debounce(value, now);
+            (self.average(value, new_scroll), decision)
+        };
+        let output = self.snap_to_detent(output);
+        let decision = if self.deferred { Decision::Deferred } else { decision };
+
+        log_trace!("{}", DecisionTrace {
+            input,
+            last_direction,
+            since_last: self.last_interval,
+            new_scroll,
+            debouncing: self.debounce_start_time.is_some(),
+            fault_bypass: self.fault_until.is_some_and(|until| now < until),
+            decision,
+            output,
+        });
+        output
+    }
+
+    /// 消抖后的值按平滑模式做平均，再经过死区
+    fn average(&mut self, value: i32, new_scroll: bool) -> i32 {
+        let value = match self.smoothing_mode {
+            SmoothingMode::DebounceOnly => value,
+            SmoothingMode::Ema => self.ema(value, new_scroll),
+        };
+        self.apply_deadzone(value)
+    }
+
+    /// 预热：新的滚动开始时先缓冲，连续 `warmup_events` 个事件方向一致后才放行
+    ///
+    /// 冷启动时第一个事件总是被当作新的滚动直接放行，滚动开始时的一个抖动尖峰会漏过去，
+    /// 还会把方向记成错误的方向。预热期间不经过消抖，确定方向后缓冲中同方向的值一起放行、
+    /// 相反方向的值视为抖动丢弃，消抖从确定的方向开始。单独一格的滚动凑不满事件数，
+    /// 由 `take_warmup_due` 在 `debounce_time` 后放行。返回 None 表示不在预热中。
+    fn warm_up(&mut self, value: i32, now: Instant, new_scroll: bool) -> Option<i32> {
+        if value == 0 || (self.warmup.is_empty() && (self.warmup_events <= 1 || !new_scroll)) {
+            return None;
+        }
+        if self.warmup.is_empty() {
+            self.warmup_until = Some(now + self.debounce_time.get(value.signum()));
+        }
+        self.warmup.push(value);
+
+        let count = self.warmup_events.max(1) as usize;
+        let direction = value.signum();
+        let consistent = self.warmup.len() >= count
+            && self.warmup[self.warmup.len() - count..].iter().all(|v| v.signum() == direction);
+        if consistent {
+            log_debug!("预热中连续 {} 个事件方向为 {}，放行缓冲的滚动", count, direction);
+            Some(self.release_warmup(direction, now))
+        } else {
+            self.deferred = true;
+            Some(0)
         }
     }
 
-    pub fn smooth(&mut self, value: i32, now: Instant) -> i32 {
+    /// 放行预热缓冲中 `direction` 方向的值，从该方向开始新的滚动
+    fn release_warmup(&mut self, direction: i32, now: Instant) -> i32 {
+        let output = self.warmup
+            .drain(..)
+            .filter(|value| value.signum() == direction)
+            .fold(0i32, |total, value| total.saturating_add(value));
+        self.warmup_until = None;
+        self.is_scrolling = true;
+        self.last_direction = direction;
+        self.last_scroll_time = now;
+        self.debounce_start_time = None;
+        self.filtered_reverses = 0;
+        output
+    }
+
+    /// 预热缓冲最晚放行的时间，没有缓冲时返回 None
+    pub fn warmup_deadline(&self) -> Option<Instant> {
+        self.warmup_until
+    }
+
+    /// 预热到期仍未确定方向时（例如只滚动了一格）放行缓冲的滚动，返回应当发送的值
+    pub fn take_warmup_due(&mut self) -> Option<i32> {
+        match self.warmup_until {
+            Some(until) if self.clock.now() >= until => self.take_warmup(),
+            _ => None,
+        }
+    }
+
+    /// 不等到期，立即放行预热缓冲的滚动，返回应当发送的值
+    ///
+    /// 方向取缓冲值的总和，总和为 0 时取最后一个事件的方向，相反方向的值丢弃。
+    pub fn take_warmup(&mut self) -> Option<i32> {
+        if self.warmup.is_empty() {
+            return None;
+        }
+        let now = self.clock.now();
+        let total = self.warmup.iter().fold(0i32, |total, &value| total.saturating_add(value));
+        let direction = match total.signum() {
+            0 => self.warmup.last().map_or(0, |value| value.signum()),
+            direction => direction,
+        };
+        log_debug!("预热到期，按方向 {} 放行缓冲的 {} 个事件", direction, self.warmup.len());
+        let output = self.release_warmup(direction, now);
+        let output = self.average(output, true);
+        let output = self.snap_to_detent(output);
+        (output != 0).then_some(output)
+    }
+
+    /// 死区：绝对值小于 `deadzone` 的输出不立即发送，累积到下一次
+    ///
+    /// 不区分方向，累积量（加上本次的值）达到死区后一次发送，被推迟的距离不会丢失。
+    /// 被过滤的抖动（0）不影响累积量。
+    fn apply_deadzone(&mut self, value: i32) -> i32 {
+        if self.deadzone <= 0 || value == 0 {
+            return value;
+        }
+
+        let total = self.deadzone_carry.saturating_add(value);
+        if total.saturating_abs() < self.deadzone {
+            log_debug!("滚动值 {} 在死区 {} 内，累积为 {}", value, self.deadzone, total);
+            self.deadzone_carry = total;
+            self.deferred = true;
+            return 0;
+        }
+        self.deadzone_carry = 0;
+        total
+    }
+
+    /// 刻度取整：输出四舍五入到 `snap_step` 的整数倍，余量（可能为负）留到下一次
+    ///
+    /// 有刻度的滚轮每格报告的高分辨率值带有噪声（例如 118、123），取整后每格正好输出
+    /// 一个刻度，不会停在两个刻度之间；累积的余量保证总距离不变。按四舍五入而不是
+    /// 等凑满整刻度，略小于一格的报告不会被推迟到下一格。不足半个刻度时视为推迟。
+    fn snap_to_detent(&mut self, value: i32) -> i32 {
+        if self.snap_step <= 0 || value == 0 {
+            return value;
+        }
+
+        let total = self.snap_carry.saturating_add(value);
+        let half = self.snap_step / 2;
+        let detents = if total >= 0 {
+            total.saturating_add(half) / self.snap_step
+        } else {
+            total.saturating_sub(half) / self.snap_step
+        };
+        let output = detents.saturating_mul(self.snap_step);
+        self.snap_carry = total.saturating_sub(output);
+        if output == 0 {
+            log_debug!("滚动值 {} 不足半个刻度，累积为 {}", value, total);
+            self.deferred = true;
+        }
+        output
+    }
+
+    /// 最近一次 `smooth` 调用的输出是否因死区、刻度取整或预热被推迟（而不是被当作抖动过滤）
+    pub fn deferred(&self) -> bool {
+        self.deferred
+    }
+
+    /// 距上次事件 `interval` 后的事件是否是新的滚动开始
+    ///
+    /// 超过消抖时间即为新的滚动；设置了连续滚动窗口且之前有滚动方向时，
+    /// 要超过该窗口才算真正的停顿，慢速但连续的滚动保持方向跟踪。
+    /// 还没有滚动方向时（刚启动）没有可以比较的方向，总是新的滚动。
+    fn is_new_scroll(&self, interval: Duration) -> bool {
+        if self.last_direction == 0 {
+            return true;
+        }
+        if interval <= self.debounce_time.get(self.last_direction) {
+            return false;
+        }
+        let continuing = self.last_direction != 0 && interval <= self.continuation_window;
+        !continuing
+    }
+
+    /// 记录一个绕过消抖直接发送的滚动值
+    ///
+    /// 按有效滚动更新方向和时间，之后的小幅反向仍按这次滚动判断是否为抖动；
+    /// EMA 从这个值重新开始平均。
+    fn pass_through(&mut self, value: i32, now: Instant) {
+        self.is_scrolling = true;
+        self.last_direction = value.signum();
+        self.last_scroll_time = now;
+        self.debounce_start_time = None;
+        self.filtered_reverses = 0;
+        self.ema_value = value as f64;
+        self.ema_carry = 0.0;
+    }
+
+    /// 滚动状态保留的最长空闲时间
+    ///
+    /// 超过消抖超时后下一个事件不会再被当作抖动；设置了更长的连续滚动窗口时
+    /// 要等窗口结束，之前的方向跟踪才不再需要。
+    fn idle_timeout(&self) -> Duration {
+        self.debounce_timeout
+            .max(self.continuation_window)
+            .max(self.debounce_time.get(0))
+    }
+
+    /// 需要调用 `tick` 清理滚动状态的时间，已经空闲时返回 None
+    pub fn idle_deadline(&self) -> Option<Instant> {
+        let active = self.is_scrolling || self.last_direction != 0 || self.debounce_start_time.is_some();
+        if active {
+            Some(self.last_scroll_time + self.idle_timeout())
+        } else {
+            None
+        }
+    }
+
+    /// 定时调用：滚轮空闲超过 `idle_timeout` 后清除滚动方向、消抖和 EMA 状态，
+    /// 空闲后的第一个事件总是从干净的状态开始判断。死区内累积的距离保留到下一次滚动，
+    /// 刻度取整的余量清除。
+    pub fn tick(&mut self) {
+        let deadline = match self.idle_deadline() {
+            Some(deadline) => deadline,
+            None => return,
+        };
+        if self.clock.now() < deadline {
+            return;
+        }
+
+        log_debug!("滚轮空闲超过 {:?}，清除滚动状态", self.idle_timeout());
+        self.is_scrolling = false;
+        self.last_direction = 0;
+        self.debounce_start_time = None;
+        self.filtered_reverses = 0;
+        self.ema_value = 0.0;
+        self.ema_carry = 0.0;
+        self.snap_carry = 0;
+    }
+
+    /// 最近一次 `smooth` 调用与上一次之间的时间间隔
+    pub fn last_interval(&self) -> Duration {
+        self.last_interval
+    }
+
+    /// 设置每个事件相当于参考回报率下的几次报告（见 `PollingRateEstimator::report_periods`）
+    pub fn set_report_periods(&mut self, periods: f64) {
+        self.report_periods = periods;
+    }
+
+    /// 指数滑动平均：新值按 `ema_alpha` 的权重混入平均值，输出平均值的整数部分，
+    /// 小数部分累积到下一次，整个滚动过程中不会因取整丢失距离。
+    /// 新的滚动开始或方向改变时重新开始平均。
+    ///
+    /// `ema_alpha` 是参考回报率下每次报告的权重，每个事件相当于 n 次参考报告时
+    /// 权重为 `1 - (1 - ema_alpha)^n`，平滑的时间常数不随回报率变化。
+    fn ema(&mut self, value: i32, new_scroll: bool) -> i32 {
+        // 被过滤的抖动不参与平均
+        if value == 0 {
+            return 0;
+        }
+
+        let value = value as f64;
+        if new_scroll || value.signum() != self.ema_value.signum() {
+            self.ema_value = value;
+            self.ema_carry = 0.0;
+        } else {
+            let alpha = if self.report_periods == 1.0 {
+                self.ema_alpha
+            } else {
+                1.0 - (1.0 - self.ema_alpha).powf(self.report_periods)
+            };
+            self.ema_value += alpha * (value - self.ema_value);
+        }
+
+        let output = self.ema_value + self.ema_carry;
+        let whole = output.trunc();
+        self.ema_carry = output - whole;
+        whole as i32
+    }
+
+    /// 对一个高分辨率滚动值做消抖，返回应当发送的值（0 表示过滤）
+    ///
+    /// 判定顺序：
+    /// `debounce_time` 和 `jitter_distance_threshold` 取当前滚动方向（`last_direction`）的值。
+    ///
+    /// 1. 距上次事件超过 `debounce_time`（设置了 `continuation_window` 时为超过该窗口）：
+    ///    视为新的滚动开始，直接放行并记录方向；
+    /// 2. 与上次方向相同：正常滚动，直接放行；
+    /// 3. 方向相反且处于故障保护期：放行并切换方向；
+    /// 4. 方向相反且本次消抖已持续超过 `debounce_timeout`：退出消抖，放行并切换方向；
+    /// 5. 方向相反且已连续过滤了 `max_filtered_reverses` 次反向：视为有意换向，放行并切换方向；
+    /// 6. 方向相反且距上次事件小于 `debounce_timeout`：视为抖动，过滤；
+    /// 7. 方向相反且距上次事件不小于 `debounce_timeout`：距离不超过
+    ///    `jitter_distance_threshold` 时仍视为抖动，否则视为有意的换向并放行。
+    ///
+    /// `now` 由 `smooth` 从时钟取得，相同的 (值, 时间) 序列总是得到相同的结果。
+    /// 同时返回所走的判定分支，用于 trace 日志。
+    fn debounce(&mut self, value: i32, now: Instant) -> (i32, Decision) {
         // 获取当前方向
         let direction = if value > 0 { 1 } else if value < 0 { -1 } else { 0 };
         
@@ -31,14 +691,15 @@ impl WheelDebouncer {
         
         log_debug!("检测到滚动事件: 方向 {} -> {}, 距离 {}, 时间间隔 {:?}", self.last_direction, direction, value, time_since_last);
         // 检测滚动状态
-        if time_since_last > self.debounce_time {
+        if self.is_new_scroll(time_since_last) {
             // 如果长时间没有滚动事件，认为是新的滚动开始
             log_debug!("长时间没有滚动事件，认为是新的滚动开始。 时间间隔 {:?}", time_since_last);
             self.is_scrolling = true;
             self.last_direction = direction;
             self.last_scroll_time = now;
             self.debounce_start_time = None; // 重置消抖开始时间
-            return value; // 直接传递第一个滚动事件
+            self.filtered_reverses = 0;
+            return (value, Decision::NewScroll); // 直接传递第一个滚动事件
         }
         
         // 更新最后滚动时间
@@ -46,6 +707,15 @@ impl WheelDebouncer {
         
         // 检查是否是滚动结束后的反向滚动
         if direction != 0 && direction != self.last_direction {
+            // 故障保护期内不做反向过滤，直接信任新的方向
+            if self.in_fault_bypass(now) {
+                log_debug!("故障保护期内，放行反向滚动: 方向 {} -> {}", self.last_direction, direction);
+                self.last_direction = direction;
+                self.debounce_start_time = None;
+                self.filtered_reverses = 0;
+                return (value, Decision::FaultBypass);
+            }
+
             // 检查是否需要退出消抖状态
             if let Some(start_time) = self.debounce_start_time {
                 if now.duration_since(start_time) > self.debounce_timeout {
@@ -53,9 +723,20 @@ impl WheelDebouncer {
                     log_info!("消抖时间已超过超时限制，退出消抖状态: {:?}", now.duration_since(start_time));
                     self.debounce_start_time = None;
                     self.last_direction = direction;
-                    return value;
+                    self.filtered_reverses = 0;
+                    return (value, Decision::DebounceTimeout);
                 }
             }
+
+            // 连续过滤的反向达到上限，用户很可能确实在反向滚动
+            if self.max_filtered_reverses > 0 && self.filtered_reverses >= self.max_filtered_reverses {
+                log_info!("已连续过滤 {} 次反向滚动，视为有意换向: 方向 {} -> {}",
+                         self.filtered_reverses, self.last_direction, direction);
+                self.last_direction = direction;
+                self.debounce_start_time = None;
+                self.filtered_reverses = 0;
+                return (value, Decision::ReverseLimit);
+            }
             
             // 只有在消抖时间内的反向滚动才被视为抖动
             if time_since_last < self.debounce_timeout {
@@ -71,30 +752,520 @@ impl WheelDebouncer {
                     log_debug!("开始消抖，记录时间: {:?}", now);
                 }
                 
-                return 0;
+                self.filtered_reverses = self.filtered_reverses.saturating_add(1);
+                self.record_suppressed_reverse(direction, now);
+                return (0, Decision::FilterJitter);
             } else {
                 // 超过消抖时间的反向滚动，认为是用户有意识的新滚动
                 // 如果距离过小，也认为是抖动
-                if value.abs() <= 300 {
+                if value.saturating_abs() <= self.jitter_distance_threshold.get(self.last_direction) {
                     log_info!("距离过小，认为是抖动: {}", value);
-                    return 0;
+                    self.filtered_reverses = self.filtered_reverses.saturating_add(1);
+                    self.record_suppressed_reverse(direction, now);
+                    return (0, Decision::FilterDistance);
                 }
                 log_info!("检测到有效的方向改变: 方向 {} -> {}, 距离 {}, 时间间隔 {:?}", 
                          self.last_direction, direction, value, time_since_last);
                 self.is_scrolling = true;
                 self.last_direction = direction;
                 self.debounce_start_time = None; // 重置消抖开始时间
-                return value;
+                self.filtered_reverses = 0;
+                return (value, Decision::Reverse);
             }
         }
         
         // 正常滚动事件，直接传递
         if direction != 0 {
             self.last_direction = direction;
-            return value;
+            self.filtered_reverses = 0;
+            return (value, Decision::Pass);
         }
         
         // 零值事件，可能是某些设备的特殊情况
-        return 0;
+        (0, Decision::Zero)
+    }
+}
+
+/// 滚轮片段合并器（`merge_window_ms`）
+///
+/// 高回报率设备把一个物理刻度拆成多个事件组，每组只有很小的高分辨率值，消抖器逐个
+/// 判断时容易误判方向。合并器把第一个片段之后窗口内连续到达的同方向片段加在一起，
+/// 窗口结束、方向改变或凑满一个刻度时再交给消抖器。
+#[derive(Default)]
+pub struct FragmentMerger {
+    value: i32,
+    deadline: Option<Instant>,
+}
+
+impl FragmentMerger {
+    /// 加入一个片段，按顺序返回需要立即交给消抖器的值：方向相反时先返回之前缓冲的值，
+    /// 缓冲的值凑满 `step`（一个刻度）时不再等待窗口结束
+    pub fn push(&mut self, value: i32, now: Instant, window: Duration, step: i32) -> [Option<i32>; 2] {
+        let mut ready = [None, None];
+        if self.value != 0 && self.value.signum() != value.signum() {
+            ready[0] = self.take();
+        }
+        if self.value == 0 {
+            self.deadline = Some(now + window);
+        }
+        self.value = self.value.saturating_add(value);
+        if self.value.saturating_abs() >= step {
+            ready[1] = self.take();
+        }
+        ready
+    }
+
+    /// 取出缓冲的值
+    pub fn take(&mut self) -> Option<i32> {
+        self.deadline = None;
+        let value = std::mem::take(&mut self.value);
+        (value != 0).then_some(value)
+    }
+
+    /// 窗口已经结束时取出缓冲的值
+    pub fn take_due(&mut self, now: Instant) -> Option<i32> {
+        match self.deadline {
+            Some(deadline) if now >= deadline => self.take(),
+            _ => None,
+        }
+    }
+
+    /// 当前窗口结束的时间
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+/// 倾斜滚轮的重复限制（`hwheel_repeat_ms`）
+///
+/// 按住倾斜滚轮时设备会以很高的频率连续报告同方向的 REL_HWHEEL，消抖器只过滤反向，
+/// 拦不住这种重复。第一个事件总是放行，之后距上次放行不足 `interval` 的同方向事件
+/// 直接丢弃，方向改变时立即放行。
+#[derive(Default)]
+pub struct RepeatLimiter {
+    direction: i32,
+    last_accepted: Option<Instant>,
+}
+
+impl RepeatLimiter {
+    /// 判断一个水平滚轮值是否放行，`interval` 为 0 时总是放行
+    pub fn accept(&mut self, value: i32, now: Instant, interval: Duration) -> bool {
+        if interval.is_zero() || value == 0 {
+            return true;
+        }
+        let direction = value.signum();
+        if direction == self.direction && self.last_accepted.is_some_and(|last| now.duration_since(last) < interval) {
+            return false;
+        }
+        self.direction = direction;
+        self.last_accepted = Some(now);
+        true
+    }
+}
+
+/// 按键消抖器
+///
+/// 磨损的微动开关按一次可能产生两次点击。松开后 `click_debounce` 内再次按下的
+/// 事件会被丢弃，与之配对的松开事件也一并丢弃。只过滤"松开后很快又按下"的
+/// 情况，按住不放（例如拖拽）永远不会被过滤。
+pub struct ButtonDebouncer {
+    click_debounce: Duration,
+    last_release: HashMap<EV_KEY, Instant>,
+    suppressed: HashSet<EV_KEY>,
+}
+
+impl ButtonDebouncer {
+    pub fn new(click_debounce: Duration) -> Self {
+        ButtonDebouncer {
+            click_debounce,
+            last_release: HashMap::new(),
+            suppressed: HashSet::new(),
+        }
+    }
+
+    /// 运行时更新按键消抖时间
+    pub fn update_params(&mut self, click_debounce: Duration) {
+        self.click_debounce = click_debounce;
+    }
+
+    /// 判断按键事件是否应该转发
+    pub fn accept(&mut self, code: EV_KEY, value: i32, now: Instant) -> bool {
+        if self.click_debounce.is_zero() {
+            return true;
+        }
+
+        match value {
+            1 => {
+                if let Some(&released) = self.last_release.get(&code) {
+                    let since_release = now.duration_since(released);
+                    if since_release < self.click_debounce {
+                        log_info!("  [已过滤] {:?} 松开后 {:?} 内再次按下，可能是开关抖动", code, since_release);
+                        self.suppressed.insert(code);
+                        return false;
+                    }
+                }
+                true
+            }
+            0 => {
+                self.last_release.insert(code, now);
+                // 被过滤的按下对应的松开也一并过滤
+                !self.suppressed.remove(&code)
+            }
+            _ => !self.suppressed.contains(&code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    /// 用默认配置（经 `adjust` 修改）创建垂直滚轮消抖器，时钟停在创建时刻
+    fn debouncer(adjust: impl FnOnce(&mut Config)) -> (WheelDebouncer<MockClock>, MockClock, Instant) {
+        let mut config = Config::default();
+        adjust(&mut config);
+        let start = Instant::now();
+        let clock = MockClock::new(start);
+        let debouncer = WheelDebouncer::with_clock(DebounceParams::from_config(&config, false), clock.clone());
+        (debouncer, clock, start)
+    }
+
+    /// 按 (距创建的毫秒数, 高分辨率值) 依次调用 `smooth`，返回每次的输出
+    fn scroll(debouncer: &mut WheelDebouncer<MockClock>, clock: &MockClock, start: Instant, events: &[(u64, i32)]) -> Vec<i32> {
+        events
+            .iter()
+            .map(|&(ms, value)| {
+                clock.set(start + Duration::from_millis(ms));
+                debouncer.smooth(value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_direction_scroll_passes() {
+        let (mut debouncer, clock, start) = debouncer(|_| {});
+        let output = scroll(&mut debouncer, &clock, start, &[(1000, 120), (1010, 120), (1020, 240), (1030, 120)]);
+        assert_eq!(output, [120, 120, 240, 120]);
+    }
+
+    #[test]
+    fn reverse_within_timeout_is_filtered() {
+        let (mut debouncer, clock, start) = debouncer(|_| {});
+        let output = scroll(&mut debouncer, &clock, start, &[(1000, 120), (1010, 120), (1020, -120), (1030, 120)]);
+        assert_eq!(output, [120, 120, 0, 120]);
+    }
+
+    #[test]
+    fn debouncing_ends_after_timeout() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.debounce_timeout_ms = 200);
+        let mut events = vec![(1000, 120)];
+        // 1010ms 开始消抖，之后每 50ms 一次反向，都在 debounce_timeout（200ms）内
+        events.extend((0..5).map(|i| (1010 + i * 50, -120)));
+        // 距消抖开始超过 200ms 的反向放行
+        events.push((1220, -120));
+        let output = scroll(&mut debouncer, &clock, start, &events);
+        assert_eq!(output, [120, 0, 0, 0, 0, 0, -120]);
+        // 之后按新的方向滚动
+        assert_eq!(scroll(&mut debouncer, &clock, start, &[(1230, -120)]), [-120]);
+    }
+
+    #[test]
+    fn reverse_after_pause_starts_new_scroll() {
+        let (mut debouncer, clock, start) = debouncer(|_| {});
+        let output = scroll(&mut debouncer, &clock, start, &[(1000, 120), (1100, -120)]);
+        assert_eq!(output, [120, -120]);
+    }
+
+    #[test]
+    fn slow_reverse_is_filtered_by_distance() {
+        // 连续滚动窗口内，超过 debounce_timeout 的反向按距离判断
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.continuation_window_ms = 1000);
+        let output = scroll(&mut debouncer, &clock, start, &[(1000, 120), (1400, -120), (1800, -480)]);
+        // 不超过 jitter_distance_threshold（300）的反向视为抖动，更大的反向放行
+        assert_eq!(output, [120, 0, -480]);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn ema_time_constant_follows_polling_rate() {
+        let (mut slow, _, _) = debouncer(|config| config.wheel.smoothing_mode = SmoothingMode::Ema);
+        let (mut fast, _, _) = debouncer(|config| config.wheel.smoothing_mode = SmoothingMode::Ema);
+        fast.set_report_periods(0.125);
+
+        // 同样的物理滚动：125 Hz 设备每次报告的值，1000 Hz 设备拆成 8 次报告
+        slow.ema(960, true);
+        slow.ema(480, false);
+        fast.ema(120, true);
+        for _ in 0..8 {
+            fast.ema(60, false);
+        }
+
+        // 经过相同的时间，平均值向新值靠近的比例相同
+        assert!((slow.ema_value / 8.0 - fast.ema_value).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dial_ema_time_constant_follows_polling_rate() {
+        let mut config = Config::default();
+        config.wheel.smoothing_mode = SmoothingMode::Ema;
+        let dial = || WheelDebouncer::with_clock(DebounceParams::for_dial(&config), MockClock::new(Instant::now()));
+        let (mut slow, mut fast) = (dial(), dial());
+        fast.set_report_periods(0.125);
+
+        // 旋钮同样按回报率换算平滑的时间常数
+        slow.ema(16, true);
+        slow.ema(8, false);
+        fast.ema(2, true);
+        for _ in 0..8 {
+            fast.ema(1, false);
+        }
+        assert!((slow.ema_value / 8.0 - fast.ema_value).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sustained_reverse_is_trusted_after_max_filtered_reverses() {
+        let events = [(1000, 120), (1010, -120), (1020, -120), (1030, -120), (1040, -120), (1050, -120)];
+
+        let (mut limited, clock, start) = debouncer(|config| config.wheel.max_filtered_reverses = 3);
+        // 连续过滤 3 次后放行第 4 次反向，之后按新的方向滚动
+        assert_eq!(scroll(&mut limited, &clock, start, &events), [120, 0, 0, 0, -120, -120]);
+        assert_eq!(scroll(&mut limited, &clock, start, &[(1060, 120)]), [0]);
+
+        // 不限制时要等到消抖超时
+        let (mut unlimited, clock, start) = debouncer(|_| {});
+        assert_eq!(scroll(&mut unlimited, &clock, start, &events), [120, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn passed_scroll_resets_filtered_reverse_count() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.max_filtered_reverses = 2);
+        // 中间有一次正常滚动，被过滤的反向不连续，不会触发放行
+        let output = scroll(&mut debouncer, &clock, start, &[(1000, 120), (1010, -120), (1020, 120), (1030, -120), (1040, 120)]);
+        assert_eq!(output, [120, 0, 120, 0, 120]);
+    }
+
+    #[test]
+    fn repeated_suppressed_reverses_enter_fault_bypass() {
+        let (mut debouncer, clock, start) = debouncer(|_| {});
+        let mut events = vec![(1000, 120)];
+        // 1000ms 窗口内连续过滤 6 次同向的反向，达到默认阈值
+        events.extend((0..6).map(|i| (1010 + i * 10, -120)));
+        assert_eq!(scroll(&mut debouncer, &clock, start, &events), [120, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(debouncer.fault_until, Some(start + Duration::from_millis(1060 + 5000)));
+
+        // 还没到消抖超时，下一次反向因故障保护直接放行
+        clock.set(start + Duration::from_millis(1070));
+        assert_eq!(debouncer.debounce(-120, clock.now()), (-120, Decision::FaultBypass));
+    }
+
+    #[test]
+    fn suppressed_reverses_outside_window_do_not_trip() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.fault_window_ms = 100);
+        let mut events = vec![(1000, 120)];
+        // 每 30ms 一次反向，100ms 窗口内最多 4 次，早的记录过期后不再计数
+        events.extend((0..8).map(|i| (1010 + i * 30, -120)));
+        assert_eq!(scroll(&mut debouncer, &clock, start, &events), [120, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(debouncer.fault_until, None);
+    }
+
+    #[test]
+    fn suppressed_reverse_direction_change_resets_count() {
+        let (mut debouncer, clock, start) = debouncer(|_| {});
+        // 三次短促的滚动，每次被过滤 3 个反向，但被过滤的方向交替变化
+        let events = [
+            (1000, 120), (1010, -120), (1020, -120), (1030, -120),
+            (1100, -120), (1110, 120), (1120, 120), (1130, 120),
+            (1200, 120), (1210, -120), (1220, -120), (1230, -120),
+        ];
+        assert_eq!(scroll(&mut debouncer, &clock, start, &events), [120, 0, 0, 0, -120, 0, 0, 0, 120, 0, 0, 0]);
+        assert_eq!(debouncer.fault_until, None);
+    }
+
+    #[test]
+    fn reverse_filtering_resumes_after_fault_cooldown() {
+        let (mut debouncer, clock, start) = debouncer(|_| {});
+        let mut events = vec![(1000, 120)];
+        events.extend((0..6).map(|i| (1010 + i * 10, -120)));
+        scroll(&mut debouncer, &clock, start, &events);
+        // 保护期到 6060ms 结束：之前的反向放行，之后的反向恢复过滤
+        let output = scroll(&mut debouncer, &clock, start, &[(6000, 120), (6050, -120), (6090, 120)]);
+        assert_eq!(output, [120, -120, 0]);
+        assert_eq!(debouncer.fault_until, None);
+    }
+
+    #[test]
+    fn ordinary_jitter_does_not_trip_fault_detection() {
+        let (mut debouncer, clock, start) = debouncer(|_| {});
+        // 两秒内每 250ms 拨动一次滚轮，每次拨动中夹着一次反向抖动
+        let gesture = [(0, 120), (20, 120), (30, -120), (40, 120), (60, 120), (80, 120)];
+        for i in 0..8 {
+            let events: Vec<_> = gesture.iter().map(|&(ms, value)| (1000 + i * 250 + ms, value)).collect();
+            assert_eq!(scroll(&mut debouncer, &clock, start, &events), [120, 120, 0, 120, 120, 120]);
+        }
+        assert_eq!(debouncer.fault_until, None);
+    }
+
+    #[test]
+    fn tick_clears_state_after_idle_timeout() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.continuation_window_ms = 500);
+        assert_eq!(debouncer.idle_deadline(), None);
+        assert_eq!(scroll(&mut debouncer, &clock, start, &[(1000, 120), (1010, -120)]), [120, 0]);
+
+        // 空闲时间取消抖超时和连续滚动窗口中较长的一个
+        let deadline = debouncer.idle_deadline().expect("滚动中应有空闲截止时间");
+        clock.set(deadline - Duration::from_millis(1));
+        debouncer.tick();
+        assert_eq!(debouncer.idle_deadline(), Some(deadline));
+
+        clock.set(deadline);
+        debouncer.tick();
+        assert_eq!(debouncer.idle_deadline(), None);
+
+        // 恢复滚动时从干净的状态开始，反方向也直接放行，之后按新方向消抖
+        let resume = (deadline - start).as_millis() as u64 + 10;
+        let output = scroll(&mut debouncer, &clock, start, &[(resume, -120), (resume + 10, 120), (resume + 20, -120)]);
+        assert_eq!(output, [-120, 0, -120]);
+    }
+
+    #[test]
+    fn reverse_within_continuation_window_is_filtered_without_tick() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.continuation_window_ms = 500);
+        // 停顿超过消抖超时但仍在连续滚动窗口内，没有 tick 清理时仍当作同一次滚动
+        assert_eq!(scroll(&mut debouncer, &clock, start, &[(1000, 120), (1400, -120)]), [120, 0]);
+    }
+
+    #[test]
+    fn debounce_time_follows_scroll_direction() {
+        let adjust = |config: &mut Config| {
+            config.wheel.debounce_time_up_ms = Some(200);
+        };
+        // 向上滚动过程中，150ms 后的反向仍在 200ms 的消抖时间内
+        let (mut up, clock, start) = debouncer(adjust);
+        assert_eq!(scroll(&mut up, &clock, start, &[(1000, 120), (1150, -120)]), [120, 0]);
+
+        // 向下滚动沿用统一的 50ms，150ms 后的反向是新的滚动
+        let (mut down, clock, start) = debouncer(adjust);
+        assert_eq!(scroll(&mut down, &clock, start, &[(1000, -120), (1150, 120)]), [-120, 120]);
+    }
+
+    #[test]
+    fn jitter_distance_threshold_follows_scroll_direction() {
+        let adjust = |config: &mut Config| {
+            config.wheel.continuation_window_ms = 1000;
+            config.wheel.jitter_distance_threshold_up = Some(600);
+            config.wheel.jitter_distance_threshold_down = Some(0);
+        };
+        // 向上滚动中 480 的反向不超过 600，视为抖动
+        let (mut up, clock, start) = debouncer(adjust);
+        assert_eq!(scroll(&mut up, &clock, start, &[(1000, 120), (1400, -480)]), [120, 0]);
+
+        // 向下滚动不按距离过滤，超过消抖超时的反向直接放行
+        let (mut down, clock, start) = debouncer(adjust);
+        assert_eq!(scroll(&mut down, &clock, start, &[(1000, -120), (1400, 120)]), [-120, 120]);
+    }
+
+    #[test]
+    fn unified_settings_apply_to_both_directions() {
+        let mut config = Config::default();
+        config.wheel.debounce_time_ms = 80;
+        config.wheel.jitter_distance_threshold = 240;
+        let params = DebounceParams::from_config(&config, false);
+        assert_eq!(params.debounce_time, PerDirection::both(Duration::from_millis(80)));
+        assert_eq!(params.jitter_distance_threshold, PerDirection::both(240));
+
+        // 只设置一个方向时，另一个方向沿用统一的值
+        config.wheel.debounce_time_down_ms = Some(30);
+        let params = DebounceParams::from_config(&config, false);
+        assert_eq!(params.debounce_time, PerDirection { up: Duration::from_millis(80), down: Duration::from_millis(30) });
+
+        // 水平滚轮不按方向区分
+        let params = DebounceParams::from_config(&config, true);
+        assert_eq!(params.debounce_time, PerDirection::both(config.get_h_debounce_time()));
+    }
+
+    #[test]
+    fn snap_to_detent_rounds_noisy_reports() {
+        let (mut up, clock, start) = debouncer(|config| config.wheel.snap_to_detent = true);
+        let output = scroll(&mut up, &clock, start, &[(1000, 118), (1010, 123), (1020, 119)]);
+        // 每格正好输出一个刻度，余量 +1 留在下一次
+        assert_eq!(output, [120, 120, 120]);
+
+        let (mut down, clock, start) = debouncer(|config| config.wheel.snap_to_detent = true);
+        let output = scroll(&mut down, &clock, start, &[(1000, -118), (1010, -123), (1020, -119)]);
+        assert_eq!(output, [-120, -120, -120]);
+    }
+
+    #[test]
+    fn snap_to_detent_defers_partial_detents() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.snap_to_detent = true);
+        let mut deferred = Vec::new();
+        let output: Vec<i32> = [(1000, 40), (1010, 40), (1020, 40)]
+            .iter()
+            .map(|&(ms, value)| {
+                let output = scroll(&mut debouncer, &clock, start, &[(ms, value)])[0];
+                deferred.push(debouncer.deferred());
+                output
+            })
+            .collect();
+        // 不足半个刻度时推迟，累积过半后输出整刻度，超出的部分记为负余量
+        assert_eq!(output, [0, 120, 0]);
+        assert_eq!(deferred, [true, false, true]);
+    }
+
+    #[test]
+    fn hwheel_repeat_drops_fast_same_direction_events() {
+        let mut limiter = RepeatLimiter::default();
+        let start = Instant::now();
+        let interval = Duration::from_millis(100);
+        let accepted: Vec<bool> = [(0, 1), (30, 1), (60, 1), (100, 1), (150, 1), (200, 1)]
+            .iter()
+            .map(|&(ms, value)| limiter.accept(value, start + Duration::from_millis(ms), interval))
+            .collect();
+        // 间隔从上次放行开始计算，被丢弃的事件不会推迟下一次放行
+        assert_eq!(accepted, [true, false, false, true, false, true]);
+    }
+
+    #[test]
+    fn hwheel_repeat_passes_direction_change() {
+        let mut limiter = RepeatLimiter::default();
+        let start = Instant::now();
+        let interval = Duration::from_millis(100);
+        let accepted: Vec<bool> = [(0, 1), (10, -1), (20, -1), (30, 1)]
+            .iter()
+            .map(|&(ms, value)| limiter.accept(value, start + Duration::from_millis(ms), interval))
+            .collect();
+        assert_eq!(accepted, [true, true, false, true]);
+    }
+
+    #[test]
+    fn hwheel_repeat_disabled_with_zero_interval() {
+        let mut limiter = RepeatLimiter::default();
+        let now = Instant::now();
+        assert!((0..5).all(|_| limiter.accept(1, now, Duration::ZERO)));
+    }
+
+    #[test]
+    fn warmup_drops_spike_at_scroll_start() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.warmup_events = 3);
+        let output = scroll(&mut debouncer, &clock, start, &[(1000, -120), (1005, 120), (1010, 120), (1015, 120), (1025, 120)]);
+        // 开头的反向尖峰被丢弃，确定方向后缓冲的同方向值一起放行，之后正常滚动
+        assert_eq!(output, [0, 0, 0, 360, 120]);
+        assert_eq!(debouncer.warmup_deadline(), None);
+        assert_eq!(scroll(&mut debouncer, &clock, start, &[(1035, -120)]), [0]);
+    }
+
+    #[test]
+    fn warmup_releases_single_detent_after_debounce_time() {
+        let (mut debouncer, clock, start) = debouncer(|config| config.wheel.warmup_events = 3);
+        assert_eq!(scroll(&mut debouncer, &clock, start, &[(1000, 120)]), [0]);
+        assert!(debouncer.deferred());
+
+        let deadline = debouncer.warmup_deadline().expect("预热中应有截止时间");
+        assert_eq!(deadline, start + Duration::from_millis(1050));
+        clock.set(deadline - Duration::from_millis(1));
+        assert_eq!(debouncer.take_warmup_due(), None);
+        clock.set(deadline);
+        assert_eq!(debouncer.take_warmup_due(), Some(120));
+        assert_eq!(debouncer.warmup_deadline(), None);
+    }
+}
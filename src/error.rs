@@ -0,0 +1,112 @@
+use std::fmt;
+use std::io;
+
+/// 程序各处返回的错误
+///
+/// 消息型的变体携带完整的错误说明（包括可操作的提示），`Display` 原样输出；
+/// 调用方需要区分失败原因时按变体匹配，例如权限不足时提示用 root 运行。
+/// 打开设备、创建虚拟设备和读写事件时 libevdev/uinput 返回的错误按错误码归类：
+/// 权限不足为 `PermissionDenied`，抓取失败为 `GrabFailed`，其余为 `Io`。
+#[derive(Debug)]
+pub enum Error {
+    // 没有可用的鼠标，或者指定的设备不存在
+    DeviceNotFound(String),
+    // 有多个候选设备而没有指定使用哪一个，或者交互选择的编号无效
+    DeviceSelection(String),
+    // 设备断开或失效，并且没有启用重连
+    Disconnected(String),
+    // 多设备模式下有设备处理失败，各设备的错误已在日志中
+    DevicesFailed(String),
+    // 没有权限打开或抓取设备
+    PermissionDenied(String),
+    // 抓取设备失败：设备被其他进程占用、抓取过程中断开等
+    GrabFailed(String),
+    // 配置文件无法解析或配置值无效
+    ConfigInvalid(String),
+    // 命令行参数无效：未知选项、缺少参数、参数格式错误或选项冲突
+    InvalidArgument(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::DeviceNotFound(message)
+            | Error::DeviceSelection(message)
+            | Error::Disconnected(message)
+            | Error::DevicesFailed(message)
+            | Error::PermissionDenied(message)
+            | Error::GrabFailed(message)
+            | Error::ConfigInvalid(message)
+            | Error::InvalidArgument(message) => f.write_str(message),
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// 按错误码归类 libevdev/uinput 返回的错误，`context` 说明失败的操作
+///
+/// 权限不足时为 `PermissionDenied`，其他错误保留错误类型，加上说明后作为 `Io`。
+pub fn device_error(context: &str, e: io::Error) -> Error {
+    if is_permission_error(&e) {
+        Error::PermissionDenied(format!("{}: {}", context, e))
+    } else {
+        io::Error::new(e.kind(), format!("{}: {}", context, e)).into()
+    }
+}
+
+/// 打开或抓取设备失败的错误码是否表示权限不足
+pub fn is_permission_error(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::EACCES) | Some(libc::EPERM))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_errors_map_to_permission_denied() {
+        for errno in [libc::EACCES, libc::EPERM] {
+            let e = device_error("无法创建虚拟设备", io::Error::from_raw_os_error(errno));
+            assert!(matches!(e, Error::PermissionDenied(_)), "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn other_device_errors_keep_io_kind() {
+        let e = device_error("无法创建虚拟设备", io::Error::from_raw_os_error(libc::ENOENT));
+        match e {
+            Error::Io(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::NotFound);
+                assert!(e.to_string().starts_with("无法创建虚拟设备: "));
+            }
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn display_is_the_message() {
+        let e = Error::DeviceSelection("找到 2 个鼠标设备".to_string());
+        assert_eq!(e.to_string(), "找到 2 个鼠标设备");
+        assert!(std::error::Error::source(&e).is_none());
+
+        let e: Error = io::Error::from_raw_os_error(libc::EIO).into();
+        assert!(matches!(e, Error::Io(_)));
+        assert!(std::error::Error::source(&e).is_some());
+    }
+}
@@ -0,0 +1,103 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::signals;
+use crate::{log_debug, log_info, log_warn};
+
+// 查询焦点窗口的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// 焦点窗口的 WM_CLASS（实例名和类名），查询失败时为空
+static WINDOW_CLASSES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// 焦点窗口类名每变化一次加一，各设备线程比较自己看到的代数来决定是否切换应用配置
+static FOCUS_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// 焦点窗口变化的代数
+pub fn focus_generation() -> usize {
+    FOCUS_GENERATION.load(Ordering::SeqCst)
+}
+
+/// 当前焦点窗口的类名
+pub fn window_classes() -> Vec<String> {
+    WINDOW_CLASSES.lock().unwrap().clone()
+}
+
+/// 启动焦点窗口监视线程
+///
+/// 定期调用 xprop 查询 X11 的焦点窗口，需要 DISPLAY（以及必要时的 XAUTHORITY）
+/// 环境变量指向用户的图形会话。Wayland 下只能查到 XWayland 窗口。
+pub fn spawn_watcher() {
+    thread::spawn(|| {
+        log_info!("开始监视焦点窗口，按应用切换滚轮配置");
+        let mut warned = false;
+
+        while !signals::shutdown_requested() {
+            let classes = match query_window_classes() {
+                Ok(classes) => {
+                    warned = false;
+                    classes
+                }
+                Err(e) => {
+                    // 图形会话还没启动时会一直失败，只提示一次
+                    if !warned {
+                        log_warn!("无法查询焦点窗口，使用基础配置: {}", e);
+                        warned = true;
+                    }
+                    Vec::new()
+                }
+            };
+
+            let mut current = WINDOW_CLASSES.lock().unwrap();
+            if *current != classes {
+                log_debug!("焦点窗口类名: {:?}", classes);
+                *current = classes;
+                FOCUS_GENERATION.fetch_add(1, Ordering::SeqCst);
+            }
+            drop(current);
+
+            signals::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+/// 查询焦点窗口的 WM_CLASS
+fn query_window_classes() -> Result<Vec<String>, String> {
+    // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+    let output = xprop(&["-root", "_NET_ACTIVE_WINDOW"])?;
+    let window = match output.rsplit("# ").next() {
+        Some(window) if window.trim().starts_with("0x") => window.trim().to_string(),
+        _ => return Err(format!("无法解析 xprop 输出: {}", output.trim())),
+    };
+    // 没有焦点窗口
+    if window == "0x0" {
+        return Ok(Vec::new());
+    }
+
+    // WM_CLASS(STRING) = "Navigator", "firefox"
+    let output = xprop(&["-id", &window, "WM_CLASS"])?;
+    let classes = match output.split_once('=') {
+        Some((_, values)) => values
+            .split(',')
+            .map(|value| value.trim().trim_matches('"').to_string())
+            .filter(|value| !value.is_empty())
+            .collect(),
+        // 窗口没有设置 WM_CLASS
+        None => Vec::new(),
+    };
+    Ok(classes)
+}
+
+fn xprop(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("xprop")
+        .args(args)
+        .output()
+        .map_err(|e| format!("无法运行 xprop: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,73 @@
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use crate::signals;
+use crate::utils::{is_event_node, probe_device, wait_readable, DeviceFilter, DeviceInfo};
+use crate::{log_debug, log_error, log_info};
+
+/// 启动热插拔监听线程
+///
+/// 通过 udev 监听 input 子系统的新增事件，新出现的事件设备如果是鼠标并且满足
+/// 启动时使用的过滤条件，就把设备信息发送给多设备处理器。收到退出信号或接收端
+/// 关闭后线程结束。
+pub fn spawn_monitor(filter: DeviceFilter, sender: Sender<DeviceInfo>) {
+    thread::spawn(move || {
+        let socket = match udev::MonitorBuilder::new()
+            .and_then(|builder| builder.match_subsystem("input"))
+            .and_then(|builder| builder.listen())
+        {
+            Ok(socket) => socket,
+            Err(e) => {
+                log_error!("无法监听 udev 事件，新插入的设备不会被自动处理: {}", e);
+                return;
+            }
+        };
+        log_info!("开始监听新插入的鼠标设备");
+
+        let fd = socket.as_raw_fd();
+        while !signals::shutdown_requested() {
+            match wait_readable(fd, None) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    log_error!("监听 udev 事件失败: {}", e);
+                    return;
+                }
+            }
+
+            for event in socket.iter() {
+                if event.event_type() != udev::EventType::Add {
+                    continue;
+                }
+                let devnode = match event.devnode() {
+                    Some(devnode) if is_event_node(devnode) => devnode,
+                    _ => continue,
+                };
+                let device = match probe_device(devnode) {
+                    Ok(Some(device)) => device,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log_debug!("无法打开新设备 {}: {}", devnode.display(), e);
+                        continue;
+                    }
+                };
+
+                if !filter.matches(&device) {
+                    log_debug!(
+                        "新设备不满足过滤条件，忽略: {} ({})",
+                        device.name,
+                        device.path
+                    );
+                    continue;
+                }
+
+                log_info!("检测到新插入的鼠标: {} ({})", device.name, device.path);
+                if sender.send(device).is_err() {
+                    // 多设备处理器已经退出
+                    return;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 帮助信息和主要错误消息使用的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Chinese = 0,
+    English = 1,
+}
+
+// 全局语言，启动时按环境变量检测，加载配置后按 logging.language 重新设置
+static CURRENT_LANGUAGE: AtomicU8 = AtomicU8::new(Language::Chinese as u8);
+
+impl Language {
+    /// 解析 logging.language 的值，"auto" 按环境变量检测
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Some(Language::from_env()),
+            "zh" => Some(Language::Chinese),
+            "en" => Some(Language::English),
+            _ => None,
+        }
+    }
+
+    /// 与 gettext 相同，按 LC_ALL、LC_MESSAGES、LANG 的顺序取第一个非空的值：
+    /// zh 开头的 locale 使用中文，其他 locale（包括 C）使用英文，都没有设置时使用中文
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty());
+        match locale {
+            Some(locale) if !locale.starts_with("zh") => Language::English,
+            _ => Language::Chinese,
+        }
+    }
+}
+
+pub fn set_language(language: Language) {
+    CURRENT_LANGUAGE.store(language as u8, Ordering::SeqCst);
+}
+
+pub fn language() -> Language {
+    match CURRENT_LANGUAGE.load(Ordering::SeqCst) {
+        1 => Language::English,
+        _ => Language::Chinese,
+    }
+}
+
+/// 按当前语言格式化消息：`tr!("中文 {}", "English {}", 参数...)`，两种语言使用相同的参数
+#[macro_export]
+macro_rules! tr {
+    ($zh:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        match $crate::i18n::language() {
+            $crate::i18n::Language::English => format!($en $(, $arg)*),
+            $crate::i18n::Language::Chinese => format!($zh $(, $arg)*),
+        }
+    };
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{Config, ConfigOverrides};
+use crate::{log_error, log_info, log_warn, metrics, signals};
+
+// 客户端在这段时间内没有发送命令时断开，避免阻塞服务线程
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 各设备当前使用的配置摘要，设备名 -> 摘要，由设备线程在参数变化时更新
+static DEVICES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+// 通过 set 命令设置的运行时参数，优先于命令行参数，重新加载配置时应用
+static OVERRIDES: Mutex<ConfigOverrides> = Mutex::new(ConfigOverrides {
+    debounce_time_ms: None,
+    h_debounce_time_ms: None,
+    debounce_timeout_ms: None,
+    dry_run: false,
+});
+
+/// 更新设备的配置摘要
+pub fn publish_device(name: &str, status: String) {
+    DEVICES.lock().unwrap().insert(name.to_string(), status);
+}
+
+/// 设备不再接管时移除它的配置摘要
+pub fn remove_device(name: &str) {
+    DEVICES.lock().unwrap().remove(name);
+}
+
+/// 在命令行覆盖参数上应用 set 命令设置的运行时参数
+pub fn with_runtime_overrides(mut overrides: ConfigOverrides) -> ConfigOverrides {
+    merge(&OVERRIDES.lock().unwrap(), &mut overrides);
+    overrides
+}
+
+fn merge(runtime: &ConfigOverrides, overrides: &mut ConfigOverrides) {
+    if let Some(value) = runtime.debounce_time_ms {
+        overrides.debounce_time_ms = Some(value);
+    }
+    if let Some(value) = runtime.h_debounce_time_ms {
+        overrides.h_debounce_time_ms = Some(value);
+    }
+    if let Some(value) = runtime.debounce_timeout_ms {
+        overrides.debounce_timeout_ms = Some(value);
+    }
+}
+
+/// 启动控制套接字服务线程
+///
+/// 每行一个命令，每个命令的回复以空行结束：
+/// - `status`：计数器和各设备当前的消抖参数
+/// - `reload`：重新加载配置文件，与 SIGHUP 相同
+/// - `set <参数> <值>`：设置 debounce_time_ms、h_debounce_time_ms 或 debounce_timeout_ms，
+///   优先于配置文件和命令行参数，之后重新加载配置文件时保留
+///
+/// 设备线程在下一次被唤醒（有事件或定时任务）时应用新的参数。
+pub fn spawn_server(path: &str, config: &Config) {
+    // 上次运行留下的套接字文件会导致 bind 失败
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            log_error!("无法删除旧的控制套接字 {}: {}", path, e);
+            return;
+        }
+    }
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log_error!("无法监听控制套接字 {}: {}", path, e);
+            return;
+        }
+    };
+    // set 和 reload 会改变运行参数，只允许运行程序的用户连接
+    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+        log_warn!("无法设置控制套接字 {} 的权限: {}", path, e);
+    }
+    log_info!("控制套接字监听 {}", path);
+
+    // 用启动时的配置检查 set 设置的值，配置文件之后的修改由重新加载时的检查处理
+    let config = config.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve(stream, &config) {
+                        log_warn!("处理控制命令失败: {}", e);
+                    }
+                }
+                Err(e) => log_warn!("接受控制连接失败: {}", e),
+            }
+        }
+    });
+}
+
+fn serve(stream: UnixStream, config: &Config) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            // 客户端发送完命令后不关闭连接时超时断开
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let reply = match handle_command(&words, config) {
+            Ok(reply) => reply,
+            Err(message) => format!("error: {}\n", message),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// 执行一条命令，返回回复的内容
+fn handle_command(words: &[&str], config: &Config) -> Result<String, String> {
+    match words {
+        ["status"] => Ok(status()),
+        ["reload"] => {
+            log_info!("收到控制命令，重新加载配置");
+            signals::request_reload();
+            Ok("ok\n".to_string())
+        }
+        ["set", key, value] => {
+            set(key, value, config)?;
+            log_info!("收到控制命令，设置 {} = {}", key, value);
+            signals::request_reload();
+            Ok("ok\n".to_string())
+        }
+        _ => Err(format!(
+            "无法识别的命令 '{}'，可用命令: status, reload, set <参数> <值>",
+            words.join(" ")
+        )),
+    }
+}
+
+/// 检查并保存一个运行时参数
+fn set(key: &str, value: &str, config: &Config) -> Result<(), String> {
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("{} 的值无效: {}", key, value))?;
+
+    let mut runtime = OVERRIDES.lock().unwrap();
+    let mut updated = runtime.clone();
+    match key {
+        "debounce_time_ms" => updated.debounce_time_ms = Some(value),
+        "h_debounce_time_ms" => updated.h_debounce_time_ms = Some(value),
+        "debounce_timeout_ms" => updated.debounce_timeout_ms = Some(value),
+        _ => return Err(format!(
+            "不支持的参数 '{}'，可以设置 debounce_time_ms, h_debounce_time_ms, debounce_timeout_ms",
+            key
+        )),
+    }
+
+    // 无效的值不保存，否则之后每次重新加载配置都会失败
+    let mut overrides = config.overrides.clone();
+    merge(&updated, &mut overrides);
+    config.clone().apply_overrides(overrides)?;
+    *runtime = updated;
+    Ok(())
+}
+
+/// 计数器、运行时参数和各设备当前的消抖参数，每行一项
+fn status() -> String {
+    let mut reply = metrics::summary();
+    let runtime = OVERRIDES.lock().unwrap();
+    for (key, value) in [
+        ("debounce_time_ms", runtime.debounce_time_ms),
+        ("h_debounce_time_ms", runtime.h_debounce_time_ms),
+        ("debounce_timeout_ms", runtime.debounce_timeout_ms),
+    ] {
+        if let Some(value) = value {
+            reply.push_str(&format!("set {} {}\n", key, value));
+        }
+    }
+    for (name, status) in DEVICES.lock().unwrap().iter() {
+        reply.push_str(&format!("device \"{}\": {}\n", name, status));
+    }
+    reply
+}
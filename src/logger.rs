@@ -1,4 +1,11 @@
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::LogTarget;
 
 // 定义日志级别
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -26,6 +33,16 @@ impl LogLevel {
         }
     }
     
+    // 对应的 syslog 优先级
+    fn syslog_priority(&self) -> libc::c_int {
+        match self {
+            LogLevel::Error => libc::LOG_ERR,
+            LogLevel::Warn => libc::LOG_WARNING,
+            LogLevel::Info => libc::LOG_INFO,
+            LogLevel::Debug | LogLevel::Trace => libc::LOG_DEBUG,
+        }
+    }
+    
     // 获取当前级别的名称
     pub fn name(&self) -> &'static str {
         match self {
@@ -62,12 +79,159 @@ pub fn should_log(level: LogLevel) -> bool {
     level as u8 <= CURRENT_LOG_LEVEL.load(Ordering::SeqCst)
 }
 
+// 日志文件，未设置时输出到终端
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64, // 超过该大小时轮转，0 表示不轮转
+}
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+impl LogFile {
+    // 写入一行日志，写入前检查是否需要轮转
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.max_size > 0 && self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    // 把当前文件重命名为 `<文件名>.1`（覆盖上一次轮转的文件），重新打开一个空文件
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        fs::rename(&self.path, &rotated)?;
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+// 设置日志文件，之后的日志追加写入该文件，超过 `max_size_mb` 时轮转到 `<文件名>.1`
+pub fn set_log_file(path: &str, max_size_mb: u64) -> io::Result<()> {
+    let path = PathBuf::from(path);
+    let file = open_append(&path)?;
+    let size = file.metadata()?.len();
+    let log_file = LogFile {
+        path,
+        file,
+        size,
+        max_size: max_size_mb.saturating_mul(1024 * 1024),
+    };
+    *LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(log_file);
+    Ok(())
+}
+
+// 日志输出目标
+static LOG_TARGET: AtomicU8 = AtomicU8::new(LogTarget::Stdout as u8);
+
+// 设置日志输出目标，选择 syslog 时打开系统日志连接
+pub fn set_log_target(target: LogTarget) {
+    if target == LogTarget::Syslog {
+        unsafe {
+            libc::openlog(c"mouse_smoother".as_ptr(), libc::LOG_PID, libc::LOG_DAEMON);
+        }
+    }
+    LOG_TARGET.store(target as u8, Ordering::SeqCst);
+}
+
+fn get_log_target() -> LogTarget {
+    match LOG_TARGET.load(Ordering::SeqCst) {
+        1 => LogTarget::Stderr,
+        2 => LogTarget::Syslog,
+        _ => LogTarget::Stdout,
+    }
+}
+
+// 以对应的优先级写入系统日志
+fn write_syslog(level: LogLevel, message: &str) {
+    // 消息中不应有 NUL，万一有就截断
+    let message = message.split('\0').next().unwrap_or_default();
+    if let Ok(message) = std::ffi::CString::new(message) {
+        unsafe {
+            libc::syslog(level.syslog_priority(), c"%s".as_ptr(), message.as_ptr());
+        }
+    }
+}
+
+// 是否在每行日志前加时间戳
+static TIMESTAMPS: AtomicBool = AtomicBool::new(true);
+
+// 设置是否输出时间戳
+pub fn set_timestamps(enabled: bool) {
+    TIMESTAMPS.store(enabled, Ordering::SeqCst);
+}
+
+// 当前本地时间，ISO-8601 格式，精确到毫秒，例如 2024-05-01T12:34:56.789
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs() as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // localtime_r 是线程安全的版本
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return format!("{}.{:03}", now.as_secs(), now.subsec_millis());
+    }
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec,
+        now.subsec_millis()
+    )
+}
+
+// 输出一条日志，由日志宏调用。级别检查在宏里完成，这里只负责格式化并按目标分发
+pub fn emit(level: LogLevel, message: String) {
+    let target = get_log_target();
+    let mut log_file = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner());
+
+    // 设置了日志文件时总是写文件
+    if log_file.is_none() && target == LogTarget::Syslog {
+        drop(log_file);
+        write_syslog(level, &message);
+        return;
+    }
+
+    let line = if TIMESTAMPS.load(Ordering::SeqCst) {
+        format!("{} [{}] {}", timestamp(), level.name(), message)
+    } else {
+        format!("[{}] {}", level.name(), message)
+    };
+
+    if let Some(file) = log_file.as_mut() {
+        match file.write_line(&line) {
+            Ok(()) => return,
+            // 写文件失败时退回到终端输出，不能因为日志丢掉错误信息
+            Err(e) => eprintln!("[{}] 写入日志文件失败: {}", LogLevel::Error.name(), e),
+        }
+    }
+    drop(log_file);
+
+    if level == LogLevel::Error || target == LogTarget::Stderr {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
 // 日志宏
 #[macro_export]
 macro_rules! log_error {
     ($($arg:tt)*) => {
         if $crate::logger::should_log($crate::logger::LogLevel::Error) {
-            eprintln!("[{}] {}", $crate::logger::LogLevel::Error.name(), format!($($arg)*));
+            $crate::logger::emit($crate::logger::LogLevel::Error, format!($($arg)*));
         }
     };
 }
@@ -76,7 +240,7 @@ macro_rules! log_error {
 macro_rules! log_warn {
     ($($arg:tt)*) => {
         if $crate::logger::should_log($crate::logger::LogLevel::Warn) {
-            println!("[{}] {}", $crate::logger::LogLevel::Warn.name(), format!($($arg)*));
+            $crate::logger::emit($crate::logger::LogLevel::Warn, format!($($arg)*));
         }
     };
 }
@@ -85,7 +249,7 @@ macro_rules! log_warn {
 macro_rules! log_info {
     ($($arg:tt)*) => {
         if $crate::logger::should_log($crate::logger::LogLevel::Info) {
-            println!("[{}] {}", $crate::logger::LogLevel::Info.name(), format!($($arg)*));
+            $crate::logger::emit($crate::logger::LogLevel::Info, format!($($arg)*));
         }
     };
 }
@@ -94,7 +258,7 @@ macro_rules! log_info {
 macro_rules! log_debug {
     ($($arg:tt)*) => {
         if $crate::logger::should_log($crate::logger::LogLevel::Debug) {
-            println!("[{}] {}", $crate::logger::LogLevel::Debug.name(), format!($($arg)*));
+            $crate::logger::emit($crate::logger::LogLevel::Debug, format!($($arg)*));
         }
     };
 }
@@ -103,7 +267,7 @@ macro_rules! log_debug {
 macro_rules! log_trace {
     ($($arg:tt)*) => {
         if $crate::logger::should_log($crate::logger::LogLevel::Trace) {
-            println!("[{}] {}", $crate::logger::LogLevel::Trace.name(), format!($($arg)*));
+            $crate::logger::emit($crate::logger::LogLevel::Trace, format!($($arg)*));
         }
     };
 } 
\ No newline at end of file
@@ -1,370 +1,2230 @@
-use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
-use evdev_rs::{Device, DeviceWrapper, GrabMode, InputEvent, ReadFlag, UInputDevice, UninitDevice};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_MSC, EV_REL, EV_SYN};
+use evdev_rs::{
+    Device, DeviceWrapper, InputEvent, ReadFlag, ReadStatus, UInputDevice, UninitDevice,
+};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::File;
+use std::fs::OpenOptions;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::thread;
 use std::time::{Duration, Instant};
 
 // 导入模块
+mod benchmark;
+mod calibrate;
+mod clock;
+mod completions;
 mod config;
+mod daemon;
 mod debouncer;
+mod error;
+#[cfg(feature = "x11")]
+mod focus;
+#[cfg(feature = "hotplug")]
+mod hotplug;
+mod i18n;
+#[cfg(feature = "ipc")]
+mod ipc;
 mod logger;
+mod metrics;
+mod polling;
+mod signals;
+mod simulate;
+mod sink;
 mod utils;
 
-use config::Config;
-use debouncer::WheelDebouncer;
+use calibrate::Calibrator;
+use config::{Config, ConfigOverrides, LogTarget, WheelBinding};
+use debouncer::{ButtonDebouncer, DebounceParams, FragmentMerger, RepeatLimiter, WheelDebouncer};
+use error::{device_error, Error};
 use logger::{set_log_level, LogLevel};
-use utils::{find_mouse_devices, is_root, print_usage, select_device};
+use polling::PollingRateEstimator;
+use sink::{DryRunSink, EventSink, RecordingSink};
+use utils::{
+    accumulate_detents, check_uinput_access, find_mouse_devices, is_root, is_touchpad,
+    open_device_error, parse_vendor_product, print_capabilities, print_usage, print_version,
+    scale_with_carry, select_device, stdin_is_tty, wait_readable, DeviceFilter, DeviceInfo,
+    DevicePattern, EventSource, GrabbedDevice, NameMatcher,
+};
+
+// 两次滚动的间隔小于该值时才开始加速
+const ACCELERATION_WINDOW: Duration = Duration::from_millis(100);
+
+// 内核高分辨率滚轮（REL_WHEEL_HI_RES/REL_HWHEEL_HI_RES）每个刻度的单位数，见 input-event-codes.h
+const KERNEL_HI_RES_STEP: i32 = 120;
+
+// 虚拟设备默认支持的鼠标按键
+const MOUSE_BUTTONS: [EV_KEY; 8] = [
+    EV_KEY::BTN_LEFT,
+    EV_KEY::BTN_RIGHT,
+    EV_KEY::BTN_MIDDLE,
+    EV_KEY::BTN_SIDE,
+    EV_KEY::BTN_EXTRA,
+    EV_KEY::BTN_FORWARD,
+    EV_KEY::BTN_BACK,
+    EV_KEY::BTN_TASK,
+];
+
+/// 主循环结束的原因
+enum RunExit {
+    // 收到退出信号或到达运行时间限制
+    Finished,
+    // 读取设备失败，设备可能已被拔出
+    Disconnected,
+    // 设备一直可读却读不到事件，设备节点还在但已经失效
+    Stalled,
+}
+
+/// 一次读取所有已就绪事件的结果
+enum ReadOutcome {
+    // 已读到 EAGAIN，记录是否读到了任何事件
+    Drained { read_any: bool },
+    // 读取失败，设备可能已断开
+    Disconnected,
+}
+
+/// 单个滚轮轴的消抖统计
+#[derive(Default)]
+struct FilterStats {
+    passed: u64,   // 通过消抖的滚轮报告数
+    filtered: u64, // 被当作抖动过滤的滚轮报告数
+}
+
+impl FilterStats {
+    fn log_summary(&self, axis: &str) {
+        let total = self.passed + self.filtered;
+        if total == 0 {
+            return;
+        }
+        log_info!(
+            "{}: 共 {} 次滚轮报告，通过 {} 次，过滤 {} 次 ({:.1}%)",
+            axis,
+            total,
+            self.passed,
+            self.filtered,
+            self.filtered as f64 * 100.0 / total as f64
+        );
+    }
+}
 
 struct MouseSmoother {
-    input_device: Device,
-    virtual_device: UInputDevice,
-    last_event_time: Instant,
+    input_device: Box<dyn EventSource>,
+    sink: Box<dyn EventSink>, // 虚拟设备，--dry-run 时只记录将要发送的事件
     vertical_debouncer: WheelDebouncer,
     horizontal_debouncer: WheelDebouncer,
-    last_wheel_time: Instant,
-    last_wheel_value: i32,
-    last_hwheel_time: Instant,
-    last_hwheel_value: i32,
+    dial_debouncer: WheelDebouncer,
     pending_events: Vec<InputEvent>, // 存储待处理的事件
-    config: Config,
+    config: Config,                  // 实际使用的配置（基础配置 + 应用配置）
+    base_config: Config,             // 配置文件 + 设备配置 + 命令行参数，不含应用配置
+    window_classes: Vec<String>,     // 焦点窗口的类名，用于选择应用配置
+    active_profile: Option<String>,  // 当前生效的应用配置
+    #[cfg(feature = "x11")]
+    focus_generation: usize, // 已处理的焦点窗口变化代数
+    config_path: String,             // 配置文件路径，用于 SIGHUP 重新加载
+    reload_generation: usize,        // 已处理的重新加载代数
+    deadline: Option<Instant>,       // 运行截止时间（--run-for）
+    button_debouncer: ButtonDebouncer,
+    pressed_buttons: HashMap<EV_KEY, EV_KEY>, // 当前按下的物理按键 -> 按下时映射到的虚拟按键
+    button_refcounts: HashMap<EV_KEY, u32>,   // 每个虚拟按键对应的按下来源数
+    button_map: HashMap<EV_KEY, EV_KEY>,      // 按键映射
+    scroll_modifier: Option<EV_KEY>,          // 水平滚动修饰键
+    wheel_bindings: Vec<WheelBinding>,        // 滚轮按键绑定
+    held_modifiers: HashMap<EV_KEY, bool>,    // 按住的修饰键 -> 本次按住期间是否已经滚动过
+    enabled_buttons: HashSet<EV_KEY>,         // 虚拟设备支持的按键
+    polling_rate: PollingRateEstimator,
+    wheel_scale_carry: f64,               // 缩放后垂直滚轮的小数余量
+    hwheel_scale_carry: f64,              // 缩放后水平滚轮的小数余量
+    wheel_remainder: i32,                 // 尚未凑满一个刻度的垂直高分辨率值
+    hwheel_remainder: i32,                // 尚未凑满一个刻度的水平高分辨率值
+    wheel_standard_carry: f64,            // 垂直标准滚轮事件缩放后的小数余量（standard_scale）
+    wheel_hi_res_carry: f64,              // 垂直高分辨率滚轮事件缩放后的小数余量（hi_res_scale）
+    hwheel_standard_carry: f64,           // 水平标准滚轮事件缩放后的小数余量
+    hwheel_hi_res_carry: f64,             // 水平高分辨率滚轮事件缩放后的小数余量
+    dump_events: bool,                    // --dump-events：打印原始滚轮事件，不做平滑
+    calibrator: Option<Calibrator>,       // --calibrate：统计原始滚轮报告中的抖动
+    last_dump_time: Option<f64>,          // 上一个转储事件的内核时间（秒）
+    dropped_events: u64,                  // 收到 SYN_DROPPED 的次数
+    group_has_output: bool,               // 当前事件组是否已经输出了事件
+    pending_scan: Option<InputEvent>,     // 等待与随后的按键事件一起发送的 MSC_SCAN
+    suppressed_codes: HashSet<EventCode>, // 不转发的事件代码（device.suppress_codes）
+    merge_mode: bool,                     // device.grab = false：不抓取设备，只发送滚轮修正量
+    wheel_corrections: HashMap<EV_REL, i32>, // 合并模式下尚未发送的滚轮修正量
+    wheel_fragments: FragmentMerger,      // 合并窗口内尚未消抖的垂直高分辨率片段（merge_window_ms）
+    hwheel_fragments: FragmentMerger,     // 合并窗口内尚未消抖的水平高分辨率片段
+    hwheel_repeat: RepeatLimiter,         // 倾斜滚轮的重复限制（hwheel_repeat_ms）
+    wheel_output_pending: i32,            // 等待合并发送的垂直高分辨率值（output_interval_ms）
+    hwheel_output_pending: i32,           // 等待合并发送的水平高分辨率值
+    last_wheel_flush: Instant,            // 上一次合并发送滚轮值的时间
+    wheel_flush_at: Option<Instant>,      // 累积的滚轮值应当发送的时间
+    has_hi_res: bool,                     // 设备是否有 REL_WHEEL_HI_RES，没有时按刻度数消抖
+    has_h_hi_res: bool,                   // 设备是否有 REL_HWHEEL_HI_RES
+    vertical_stats: FilterStats,
+    horizontal_stats: FilterStats,
+    dial_stats: FilterStats,
 }
 
 impl MouseSmoother {
-    fn new(device_path: &str, config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
-        // 打开输入设备
-        let file = File::open(device_path)?;
-        let mut input_device = Device::new_from_file(file)?;
+    fn new(device_path: &str, config: &Config, config_path: &str) -> Result<Self, Error> {
+        // 以非阻塞方式打开输入设备，由 poll 等待事件
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(device_path)
+            .map_err(|e| open_device_error(device_path, e))?;
+        // 之后任何一步出错返回时，guard 都会释放已经成功的抓取
+        let mut input_device = GrabbedDevice::new(
+            Device::new_from_file(file).map_err(|e| open_device_error(device_path, e))?,
+        );
 
         // 获取设备名称
         let device_name = input_device.name().unwrap_or("Unknown Mouse").to_string();
-        log_info!("拦截设备: {}", device_name);
-
-        // 设置输入设备为抓取模式，这样其他程序不会收到原始事件
-        input_device.grab(GrabMode::Grab)?;
-
-        // 创建虚拟设备
-        let uinput_device = UninitDevice::new().unwrap();
-
-        // 设置设备名称
-        uinput_device.set_name(&format!("Virtual {}", device_name));
-
-        // 添加按键支持
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_LEFT), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_RIGHT), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_MIDDLE), None)?;
-        // 添加额外的鼠标按钮支持
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_SIDE), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_EXTRA), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_FORWARD), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_BACK), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_KEY(EV_KEY::BTN_TASK), None)?;
-
-        // 添加相对轴支持
-        uinput_device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_X), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_Y), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_WHEEL), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES), None)?;
-        // 添加水平滚轮支持
-        uinput_device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_HWHEEL), None)?;
-        uinput_device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES), None)?;
-
-        // 添加杂项事件支持
-        uinput_device
-            .enable_event_code(&EventCode::EV_MSC(evdev_rs::enums::EV_MSC::MSC_SCAN), None)?;
-
-        // 创建虚拟设备
-        let virtual_device = UInputDevice::create_from_device(&uinput_device)?;
-
-        log_info!("创建虚拟设备: Virtual {}", device_name);
+        // 匹配的设备配置覆盖 [wheel] 中的参数
+        let config = &config.for_device(
+            &device_name,
+            input_device.vendor_id(),
+            input_device.product_id(),
+        );
+        let dry_run = config.overrides.dry_run;
+        // 不抓取时原设备的事件照常送达，虚拟设备只发送滚轮的修正量
+        let merge_mode = !dry_run && !config.device.grab;
+        let virtual_name = config.virtual_device_name(&device_name);
+        if dry_run {
+            log_info!("试运行，只读取设备: {}", device_name);
+        } else if merge_mode {
+            log_info!("合并模式，不抓取设备: {}", device_name);
+            log_warn!(
+                "合并模式下其他程序仍会收到原始滚轮事件，按键映射、按键消抖、水平滚动修饰键和滚轮按键绑定不起作用"
+            );
+        } else {
+            log_info!("拦截设备: {}", device_name);
+            // 设置输入设备为抓取模式，这样其他程序不会收到原始事件
+            input_device.grab(device_path, &virtual_name)?;
+        }
+
+        // 虚拟设备支持的按键，包括按键映射的目标按键
+        let enabled_buttons = virtual_buttons(
+            &config.button.remap_codes().map_err(Error::ConfigInvalid)?,
+            &config.wheel_bindings().map_err(Error::ConfigInvalid)?,
+        );
+        let has_dial = input_device.has_event_code(&EventCode::EV_REL(EV_REL::REL_DIAL));
+
+        // 创建虚拟设备。触摸板的绝对坐标轴、触摸工具按键和输入属性需要原样保留，
+        // 以真实设备为模板创建；普通鼠标只声明用到的事件
+        let record_path = config.record_path(&device_name);
+        let record_path = record_path.as_deref();
+        let sink: Box<dyn EventSink> = if dry_run {
+            with_recording(Box::new(DryRunSink), &*input_device, record_path)?
+        } else if is_touchpad(&*input_device) {
+            log_info!("设备是触摸板，虚拟设备复制其全部能力");
+            input_device.set_name(&virtual_name);
+            enable_virtual_codes(&*input_device, &enabled_buttons, has_dial, config)
+                .map_err(|e| device_error("无法设置虚拟设备的事件", e))?;
+            log_info!("创建虚拟设备: {}", virtual_name);
+            let virtual_device = create_virtual_device(&*input_device)?;
+            with_recording(Box::new(virtual_device), &*input_device, record_path)?
+        } else {
+            let uinput_device = UninitDevice::new().unwrap();
+            uinput_device.set_name(&virtual_name);
+            enable_virtual_codes(&uinput_device, &enabled_buttons, has_dial, config)
+                .map_err(|e| device_error("无法设置虚拟设备的事件", e))?;
+            log_info!("创建虚拟设备: {}", virtual_name);
+            let virtual_device = create_virtual_device(&uinput_device)?;
+            with_recording(Box::new(virtual_device), &uinput_device, record_path)?
+        };
+
+        // 只有标准滚轮事件的设备直接对刻度数消抖，不伪造高分辨率值
+        let has_hi_res = input_device.has_event_code(&EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES));
+        let has_h_hi_res =
+            input_device.has_event_code(&EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES));
+        // 相对轴没有 absinfo，设备不会报告高分辨率滚轮的分辨率；内核的 hid-input 已经按设备的
+        // 分辨率倍数换算，每个刻度固定报告 120 个单位，hi_res_step 与之不同时刻度换算会出错
+        if !has_hi_res {
+            log_info!("设备没有高分辨率滚轮，按刻度数消抖");
+        } else if config.wheel.hi_res_step != KERNEL_HI_RES_STEP {
+            log_warn!(
+                "wheel.hi_res_step = {}，但内核的高分辨率滚轮每个刻度固定为 {} 个单位，刻度换算和阈值可能不准确",
+                config.wheel.hi_res_step,
+                KERNEL_HI_RES_STEP
+            );
+        } else {
+            log_info!("高分辨率滚轮每个刻度 {} 个单位", KERNEL_HI_RES_STEP);
+        }
+
+        MouseSmoother::with_source(
+            Box::new(input_device),
+            sink,
+            config,
+            config_path,
+            has_hi_res,
+            has_h_hi_res,
+        )
+    }
+
+    /// 用已经打开的输入来源和输出目标创建平滑器
+    ///
+    /// `config` 已经应用了设备配置；`has_hi_res`、`has_h_hi_res` 表示输入设备是否有
+    /// 垂直、水平高分辨率滚轮。
+    fn with_source(
+        input_device: Box<dyn EventSource>,
+        sink: Box<dyn EventSink>,
+        config: &Config,
+        config_path: &str,
+        has_hi_res: bool,
+        has_h_hi_res: bool,
+    ) -> Result<Self, Error> {
+        let merge_mode = !config.overrides.dry_run && !config.device.grab;
+        let button_map = config.button.remap_codes().map_err(Error::ConfigInvalid)?;
+        let scroll_modifier = config
+            .button
+            .scroll_modifier_code()
+            .map_err(Error::ConfigInvalid)?;
+        let wheel_bindings = config.wheel_bindings().map_err(Error::ConfigInvalid)?;
+        let enabled_buttons = virtual_buttons(&button_map, &wheel_bindings);
 
         // 创建垂直和水平滚轮的消抖器
         let vertical_debouncer =
-            WheelDebouncer::new(config.get_debounce_time(), config.get_debounce_timeout());
-
+            WheelDebouncer::new(wheel_debounce_params(config, false, has_hi_res));
         let horizontal_debouncer =
-            WheelDebouncer::new(config.get_h_debounce_time(), config.get_debounce_timeout());
+            WheelDebouncer::new(wheel_debounce_params(config, true, has_h_hi_res));
+        let dial_debouncer = WheelDebouncer::new(DebounceParams::for_dial(config));
 
         Ok(MouseSmoother {
             input_device,
-            virtual_device,
-            last_event_time: Instant::now(),
+            sink,
             vertical_debouncer,
             horizontal_debouncer,
-            last_wheel_time: Instant::now(),
-            last_wheel_value: 0,
-            last_hwheel_time: Instant::now(),
-            last_hwheel_value: 0,
+            dial_debouncer,
             pending_events: Vec::new(),
             config: config.clone(),
+            base_config: config.clone(),
+            window_classes: Vec::new(),
+            active_profile: None,
+            #[cfg(feature = "x11")]
+            focus_generation: 0,
+            config_path: config_path.to_string(),
+            reload_generation: signals::reload_generation(),
+            deadline: None,
+            button_debouncer: ButtonDebouncer::new(config.get_click_debounce_time()),
+            pressed_buttons: HashMap::new(),
+            button_refcounts: HashMap::new(),
+            button_map,
+            scroll_modifier,
+            wheel_bindings,
+            held_modifiers: HashMap::new(),
+            enabled_buttons,
+            polling_rate: PollingRateEstimator::default(),
+            wheel_scale_carry: 0.0,
+            hwheel_scale_carry: 0.0,
+            wheel_remainder: 0,
+            hwheel_remainder: 0,
+            wheel_standard_carry: 0.0,
+            wheel_hi_res_carry: 0.0,
+            hwheel_standard_carry: 0.0,
+            hwheel_hi_res_carry: 0.0,
+            dump_events: false,
+            calibrator: None,
+            last_dump_time: None,
+            dropped_events: 0,
+            group_has_output: false,
+            pending_scan: None,
+            suppressed_codes: config
+                .device
+                .suppress_event_codes()
+                .map_err(Error::ConfigInvalid)?,
+            merge_mode,
+            wheel_corrections: HashMap::new(),
+            wheel_fragments: FragmentMerger::default(),
+            hwheel_fragments: FragmentMerger::default(),
+            hwheel_repeat: RepeatLimiter::default(),
+            wheel_output_pending: 0,
+            hwheel_output_pending: 0,
+            last_wheel_flush: Instant::now(),
+            wheel_flush_at: None,
+            has_hi_res,
+            has_h_hi_res,
+            vertical_stats: FilterStats::default(),
+            horizontal_stats: FilterStats::default(),
+            dial_stats: FilterStats::default(),
         })
     }
 
-    fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// 同时平滑多个设备：每个设备一个线程，各自拥有独立的抓取设备、虚拟设备和消抖器
+    ///
+    /// 要处理的设备从 `devices` 接收：启动时扫描到的设备，以及启用 `hotplug` 特性时
+    /// 新插入的设备。单个设备出错不会影响其他设备，所有线程结束后汇总错误。
+    fn run_all(
+        devices: Receiver<DeviceInfo>,
+        config: &Config,
+        config_path: &str,
+        run_for: Option<Duration>,
+    ) -> Result<(), Error> {
+        let deadline = run_for.map(|duration| Instant::now() + duration);
+        let mut handles: Vec<(String, thread::JoinHandle<Result<(), Error>>)> = Vec::new();
+        let mut failed = Vec::new();
+
+        loop {
+            if signals::shutdown_requested() {
+                break;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
+            }
+
+            // 回收已经结束的设备线程
+            let (finished, running): (Vec<_>, Vec<_>) = handles
+                .into_iter()
+                .partition(|(_, handle)| handle.is_finished());
+            handles = running;
+            for (path, handle) in finished {
+                join_device_thread(path, handle, &mut failed);
+            }
+
+            let device = match devices.recv_timeout(Duration::from_millis(200)) {
+                Ok(device) => device,
+                Err(RecvTimeoutError::Timeout) => continue,
+                // 没有热插拔监听时，启动时的设备都已接收
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // 同一个设备可能既在启动时被扫描到，又收到了 udev 的新增事件
+            if handles.iter().any(|(path, _)| *path == device.path) {
+                continue;
+            }
+
+            let config = config.clone();
+            let config_path = config_path.to_string();
+            let path = device.path.clone();
+            let run_for =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let handle = thread::spawn(move || {
+                MouseSmoother::run_device_thread(&device, &config, &config_path, run_for)
+            });
+            handles.push((path, handle));
+        }
+
+        for (path, handle) in handles {
+            join_device_thread(path, handle, &mut failed);
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DevicesFailed(format!(
+                "{} 个设备处理失败: {}",
+                failed.len(),
+                failed.join(", ")
+            )))
+        }
+    }
+
+    /// 多设备模式下单个设备线程的入口
+    fn run_device_thread(
+        device: &DeviceInfo,
+        config: &Config,
+        config_path: &str,
+        run_for: Option<Duration>,
+    ) -> Result<(), Error> {
+        // 启用热插拔监听时，拔出后再插入的设备会被重新发现，线程本身不重连
+        if cfg!(feature = "hotplug") {
+            let mut smoother = MouseSmoother::new(&device.path, config, config_path)?;
+            if let Some(duration) = run_for {
+                smoother.stop_after(duration);
+            }
+            loop {
+                match smoother.run()? {
+                    RunExit::Finished => return Ok(()),
+                    RunExit::Disconnected => {
+                        log_info!("设备 {} 已断开，等待重新插入", device.path);
+                        return Ok(());
+                    }
+                    // 失效的设备节点没有被移除，udev 不会再报告它，由本线程重新打开
+                    RunExit::Stalled => {
+                        let config = smoother.config.clone();
+                        let deadline = smoother.deadline;
+                        if config.get_reconnect_interval().is_zero() {
+                            return Err(Error::Disconnected(format!(
+                                "设备 {} 已失效",
+                                device.path
+                            )));
+                        }
+                        drop(smoother);
+                        smoother =
+                            match MouseSmoother::reconnect(device, &config, config_path, deadline)?
+                            {
+                                Some(smoother) => smoother,
+                                None => return Ok(()),
+                            };
+                        metrics::reconnected();
+                    }
+                }
+            }
+        } else {
+            MouseSmoother::run_with_reconnect(device, config, config_path, run_for)
+        }
+    }
+
+    /// 平滑单个设备，设备断开后自动重连
+    ///
+    /// 设备被拔出后每隔 `reconnect_interval_ms` 重新扫描一次，找到名称和
+    /// vendor:product 都相同的设备（事件编号可能已经变化）后重新抓取并创建虚拟设备。
+    /// `reconnect_interval_ms` 为 0 时不重连，设备断开即返回错误。
+    fn run_with_reconnect(
+        device: &DeviceInfo,
+        config: &Config,
+        config_path: &str,
+        run_for: Option<Duration>,
+    ) -> Result<(), Error> {
+        let mut smoother = MouseSmoother::new(&device.path, config, config_path)?;
+        if let Some(duration) = run_for {
+            smoother.stop_after(duration);
+        }
+
+        loop {
+            if let RunExit::Finished = smoother.run()? {
+                return Ok(());
+            }
+
+            // 沿用运行中通过 SIGHUP 重新加载的配置和截止时间
+            let config = smoother.config.clone();
+            let deadline = smoother.deadline;
+            let interval = config.get_reconnect_interval();
+            if interval.is_zero() {
+                return Err(Error::Disconnected(format!("设备 {} 已断开", device.path)));
+            }
+
+            // 先释放旧的虚拟设备，再等待设备重新出现
+            drop(smoother);
+            smoother = match MouseSmoother::reconnect(device, &config, config_path, deadline)? {
+                Some(smoother) => smoother,
+                None => return Ok(()),
+            };
+            metrics::reconnected();
+        }
+    }
+
+    /// 等待设备重新出现并重新创建平滑器
+    ///
+    /// 收到退出信号或到达运行时间限制时返回 None。
+    fn reconnect(
+        device: &DeviceInfo,
+        config: &Config,
+        config_path: &str,
+        deadline: Option<Instant>,
+    ) -> Result<Option<Self>, Error> {
+        let interval = config.get_reconnect_interval();
+        let mut attempt: u32 = 0;
+
+        loop {
+            signals::sleep(interval);
+            if signals::shutdown_requested() {
+                log_info!("收到退出信号");
+                return Ok(None);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                log_info!("已到达运行时间限制");
+                return Ok(None);
+            }
+
+            attempt += 1;
+            log_info!(
+                "第 {} 次尝试重新连接设备: {} [{:04x}:{:04x}]",
+                attempt,
+                device.name,
+                device.vendor,
+                device.product
+            );
+
+            let devices = match find_mouse_devices() {
+                Ok(devices) => devices,
+                Err(e) => {
+                    log_warn!("扫描设备失败: {}", e);
+                    continue;
+                }
+            };
+            let found = devices.into_iter().find(|d| {
+                d.vendor == device.vendor && d.product == device.product && d.name == device.name
+            });
+
+            if let Some(found) = found {
+                // 设备节点刚出现时权限可能还没有设置好，失败后下次继续尝试
+                match MouseSmoother::new(&found.path, config, config_path) {
+                    Ok(mut smoother) => {
+                        smoother.deadline = deadline;
+                        log_info!("设备已重新连接: {}", found.path);
+                        return Ok(Some(smoother));
+                    }
+                    Err(e) => log_warn!("重新接管设备 {} 失败: {}", found.path, e),
+                }
+            }
+        }
+    }
+
+    /// 进入事件转储模式：以 CSV 格式把原始滚轮事件打印到标准输出，所有事件原样转发
+    ///
+    /// 每行为 `时间戳(秒),距上一个滚轮事件的间隔(毫秒),轴,值`，时间戳取内核事件时间。
+    fn enable_event_dump(&mut self) {
+        self.dump_events = true;
+        println!("timestamp,delta_ms,axis,value");
+    }
+
+    /// 进入校准模式：正常平滑的同时统计原始垂直滚轮报告中的抖动
+    fn enable_calibration(&mut self) {
+        self.calibrator = Some(Calibrator::default());
+    }
+
+    /// 打印一个原始滚轮事件
+    fn dump_event(&mut self, event: &InputEvent) {
+        let axis = match event.event_code {
+            EventCode::EV_REL(
+                code @ (EV_REL::REL_WHEEL
+                | EV_REL::REL_WHEEL_HI_RES
+                | EV_REL::REL_HWHEEL
+                | EV_REL::REL_HWHEEL_HI_RES),
+            ) => code,
+            _ => return,
+        };
+
+        let time = event.time.tv_sec as f64 + event.time.tv_usec as f64 / 1_000_000.0;
+        let delta_ms = self
+            .last_dump_time
+            .map_or(0.0, |last| (time - last) * 1000.0);
+        self.last_dump_time = Some(time);
+
+        println!("{:.6},{:.3},{:?},{}", time, delta_ms, axis, event.value);
+    }
+
+    /// 设置最长运行时间，到期后正常退出
+    fn stop_after(&mut self, duration: Duration) {
+        self.deadline = Some(Instant::now() + duration);
+        log_info!("将在 {:?} 后自动退出", duration);
+    }
+
+    /// 主循环
+    ///
+    /// 设备以 O_NONBLOCK 打开，没有轮询间隔：用 poll 阻塞等待设备可读，超时只取最近的
+    /// 定时任务（见 `next_timer`）和运行截止时间，空闲时不占用 CPU。设备可读后用
+    /// `ReadFlag::NORMAL` 读到 EAGAIN 为止，不使用 `ReadFlag::BLOCKING`，读取本身不会阻塞。
+    fn run(&mut self) -> Result<RunExit, Error> {
         log_info!("开始处理鼠标滚轮事件...");
         log_info!("其他鼠标事件将直接传递");
 
         // 预分配一定容量的事件缓冲区，避免频繁重新分配内存
         self.pending_events.reserve(16);
 
+        let fd = self.input_device.raw_fd();
+        // 设备开始报告可读却读不到任何事件的时间，用于检测失效的设备
+        let mut empty_since: Option<Instant> = None;
+
         loop {
-            // 读取事件
-            match self.input_device.next_event(ReadFlag::NORMAL) {
-                Ok((_, event)) => {
-                    // 打印每个收到的事件
-                    log_trace!(
-                        "收到事件: 类型={:?}, 代码={:?}, 值={}",
-                        event.event_type(),
-                        event.event_code,
-                        event.value
-                    );
+            // 收到 SIGINT/SIGTERM 时正常退出
+            if signals::shutdown_requested() {
+                log_info!("收到退出信号");
+                self.shutdown()?;
+                return Ok(RunExit::Finished);
+            }
+
+            // 计算等待超时：有运行截止时间时最多等到截止时间
+            let now = Instant::now();
+            let timeout = match self.deadline {
+                Some(deadline) => {
+                    if now >= deadline {
+                        log_info!("已到达运行时间限制");
+                        self.shutdown()?;
+                        return Ok(RunExit::Finished);
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            // 有等待合并发送的滚轮值或需要清理的滚动状态时最多等到对应的时间
+            let timeout = match self.next_timer() {
+                Some(due) => {
+                    let until_due = due.saturating_duration_since(now);
+                    Some(timeout.map_or(until_due, |t| t.min(until_due)))
+                }
+                None => timeout,
+            };
+
+            // 阻塞等待设备可读，不再轮询休眠
+            let readable = wait_readable(fd, timeout)?;
+
+            // 收到 SIGHUP 或控制命令时重新加载配置。控制命令不会打断 poll，
+            // 在被唤醒后、处理新事件之前检查
+            if signals::reload_generation() != self.reload_generation {
+                self.reload_generation = signals::reload_generation();
+                self.reload_config();
+            }
 
-                    // 检查是否是同步事件
-                    if let EventCode::EV_SYN(EV_SYN::SYN_REPORT) = event.event_code {
-                        // 处理收集到的事件组
-                        self.process_event_group()?;
-                        // 发送同步事件
-                        self.virtual_device.write_event(&event)?;
-                    } else {
-                        // 收集非同步事件
-                        self.pending_events.push(event);
+            self.flush_wheel_fragments(false)?;
+            self.flush_wheel_warmup(false)?;
+            self.flush_due_wheel_output()?;
+            self.vertical_debouncer.tick();
+            self.horizontal_debouncer.tick();
+            self.dial_debouncer.tick();
+            if !readable {
+                empty_since = None;
+                continue;
+            }
+
+            // 焦点窗口变化后切换应用配置，在处理新事件之前进行
+            #[cfg(feature = "x11")]
+            if focus::focus_generation() != self.focus_generation {
+                self.focus_generation = focus::focus_generation();
+                self.window_classes = focus::window_classes();
+                self.apply_profile();
+            }
+
+            // 设备失效但没有报错时会一直可读、每次都读到 EAGAIN，主循环空转
+            match self.read_events()? {
+                ReadOutcome::Drained { read_any: true } => empty_since = None,
+                ReadOutcome::Drained { read_any: false } => {
+                    let since = *empty_since.get_or_insert_with(Instant::now);
+                    let stall_timeout = self.config.get_stall_timeout();
+                    if !stall_timeout.is_zero() && since.elapsed() >= stall_timeout {
+                        log_warn!(
+                            "设备持续可读但 {:?} 内没有读到任何事件，设备可能已失效，重新连接",
+                            stall_timeout
+                        );
+                        self.release_after_disconnect();
+                        return Ok(RunExit::Stalled);
                     }
                 }
+                ReadOutcome::Disconnected => {
+                    self.release_after_disconnect();
+                    return Ok(RunExit::Disconnected);
+                }
+            }
+        }
+    }
+
+    /// 读取所有已就绪的事件
+    ///
+    /// libevdev 内部有缓冲，必须读到 EAGAIN 为止，否则缓冲中的事件要等到下一次设备可读
+    /// 才会被处理。读到一半的事件组（还没有收到 SYN_REPORT）的处理规则：
+    /// - EAGAIN/EINTR 没有丢失任何数据，保留已收集的事件，由之后读到的事件补全；
+    /// - 其他错误说明剩余事件已经读不到了，丢弃不完整的事件组后再返回，
+    ///   不会与之后的事件拼成一个错误的事件组
+    fn read_events(&mut self) -> Result<ReadOutcome, Error> {
+        let mut read_any = false;
+        loop {
+            match self.input_device.next_event(ReadFlag::NORMAL) {
+                Ok((ReadStatus::Success, event)) => {
+                    read_any = true;
+                    metrics::event_read();
+                    self.handle_event(event)?
+                }
+                // 内核事件缓冲区溢出，收到的是 SYN_DROPPED
+                Ok((ReadStatus::Sync, _)) => {
+                    read_any = true;
+                    self.resync()?
+                }
                 Err(e) if e.raw_os_error() == Some(libc::EAGAIN) => {
-                    // 没有事件，继续
+                    return Ok(ReadOutcome::Drained { read_any })
+                }
+                Err(e) if e.raw_os_error() == Some(libc::EINTR) => continue,
+                Err(e) => {
+                    log_warn!("读取设备事件失败，设备可能已断开: {}", e);
+                    self.discard_partial_group();
+                    return Ok(ReadOutcome::Disconnected);
+                }
+            }
+        }
+    }
+
+    /// 最近一个定时任务的时间：处理合并窗口中的滚轮片段、放行预热到期的滚动、合并发送滚轮值、
+    /// 清理空闲的滚动状态
+    fn next_timer(&self) -> Option<Instant> {
+        [
+            self.wheel_fragments.deadline(),
+            self.hwheel_fragments.deadline(),
+            self.vertical_debouncer.warmup_deadline(),
+            self.horizontal_debouncer.warmup_deadline(),
+            self.dial_debouncer.warmup_deadline(),
+            self.wheel_flush_at,
+            self.vertical_debouncer.idle_deadline(),
+            self.horizontal_debouncer.idle_deadline(),
+            self.dial_debouncer.idle_deadline(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// 内核事件缓冲区溢出（SYN_DROPPED）后重新同步设备状态
+    ///
+    /// 溢出时正在收集的事件组已经不完整，直接丢弃。之后用 libevdev 的同步模式读出
+    /// 设备当前状态与丢失前的差异（例如丢失的按键松开），按正常事件处理，
+    /// 让虚拟设备的按键状态与物理设备保持一致。
+    fn resync(&mut self) -> Result<(), Error> {
+        self.dropped_events += 1;
+        log_warn!(
+            "内核事件缓冲区溢出，丢弃不完整的事件组并重新同步（第 {} 次），处理速度可能跟不上设备",
+            self.dropped_events
+        );
+        self.discard_partial_group();
+
+        loop {
+            match self.input_device.next_event(ReadFlag::SYNC) {
+                Ok((_, event)) => {
+                    metrics::event_read();
+                    self.handle_event(event)?
                 }
+                Err(e) if e.raw_os_error() == Some(libc::EAGAIN) => return Ok(()),
+                Err(e) if e.raw_os_error() == Some(libc::EINTR) => continue,
                 Err(e) => {
-                    return Err(e.into());
+                    // 同步到一半失败，已收集的同步事件同样不完整
+                    self.discard_partial_group();
+                    return Err(device_error("重新同步设备状态失败", e));
                 }
             }
+        }
+    }
+
+    /// 丢弃还没有收到 SYN_REPORT 的事件组
+    fn discard_partial_group(&mut self) {
+        if !self.pending_events.is_empty() {
+            log_debug!("丢弃不完整的事件组（{} 个事件）", self.pending_events.len());
+            self.pending_events.clear();
+        }
+    }
+
+    /// 处理读取到的单个事件
+    fn handle_event(&mut self, event: InputEvent) -> Result<(), Error> {
+        // 打印每个收到的事件
+        log_trace!(
+            "收到事件: 类型={:?}, 代码={:?}, 值={}",
+            event.event_type(),
+            event.event_code,
+            event.value
+        );
+
+        // 转储模式下不做任何处理，原样转发
+        if self.dump_events {
+            self.dump_event(&event);
+            self.write(&event)?;
+            return Ok(());
+        }
+
+        // 检查是否是同步事件
+        if let EventCode::EV_SYN(EV_SYN::SYN_REPORT) = event.event_code {
+            self.polling_rate.observe(&event.time);
+            self.apply_polling_rate();
+            // 处理收集到的事件组，需要时由其发送同步事件
+            self.process_event_group(&event)?;
+        } else {
+            // 收集非同步事件
+            self.pending_events.push(event);
+        }
+
+        Ok(())
+    }
+
+    /// 重新加载配置文件并更新运行参数，不释放设备
+    ///
+    /// 新配置无效时记录错误并保留原有设置。
+    fn reload_config(&mut self) {
+        log_info!("重新加载配置文件: {}", self.config_path);
+
+        if !Path::new(&self.config_path).exists() {
+            log_error!("配置文件 {} 不存在，保留原有设置", self.config_path);
+            return;
+        }
+
+        let mut config = match Config::load(&self.config_path) {
+            Ok(config) => config,
+            Err(e) => {
+                log_error!("重新加载配置失败，保留原有设置: {}", e);
+                return;
+            }
+        };
+
+        // 命令行参数仍然优先于配置文件，控制命令设置的参数又优先于命令行参数
+        let overrides = self.config.overrides.clone();
+        #[cfg(feature = "ipc")]
+        let overrides = ipc::with_runtime_overrides(overrides);
+        if let Err(e) = config.apply_overrides(overrides) {
+            log_error!("重新加载配置失败，保留原有设置: {}", e);
+            return;
+        }
+        config.warn_scale_divergence();
+        let config = config.for_device(
+            self.input_device.name().unwrap_or("Unknown Mouse"),
+            self.input_device.vendor_id(),
+            self.input_device.product_id(),
+        );
+
+        self.button_debouncer
+            .update_params(config.get_click_debounce_time());
+        // suppress_codes 已在加载配置时检查过
+        self.suppressed_codes = config.device.suppress_event_codes().unwrap_or_default();
+        self.reload_button_map(&config);
+        self.scroll_modifier = config.button.scroll_modifier_code().unwrap_or(None);
+        self.reload_wheel_bindings(&config);
+        self.base_config = config;
+        self.apply_profile();
+
+        log_info!(
+            "配置已更新: 垂直消抖 {:?}, 水平消抖 {:?}, 超时 {:?}",
+            self.config.get_debounce_time(),
+            self.config.get_h_debounce_time(),
+            self.config.get_debounce_timeout()
+        );
+    }
+
+    /// 根据焦点窗口在基础配置上应用匹配的应用配置，更新消抖参数
+    fn apply_profile(&mut self) {
+        let profile = self.base_config.find_profile(&self.window_classes);
+        let profile_name = profile.map(|profile| profile.window_class.clone());
+        let config = self.base_config.with_profile(profile);
+
+        if profile_name != self.active_profile {
+            match &profile_name {
+                Some(name) => log_info!("切换到应用配置: {}", name),
+                None => log_info!("恢复基础配置"),
+            }
+            self.active_profile = profile_name;
+        }
+
+        #[cfg(feature = "ipc")]
+        ipc::publish_device(
+            self.input_device.name().unwrap_or("Unknown Mouse"),
+            format!(
+                "profile={} debounce_time_ms={} h_debounce_time_ms={} debounce_timeout_ms={} multiplier={}",
+                self.active_profile.as_deref().unwrap_or("-"),
+                config.wheel.debounce_time_ms,
+                config.wheel.h_debounce_time_ms,
+                config.wheel.debounce_timeout_ms,
+                config.wheel.multiplier
+            ),
+        );
+
+        self.vertical_debouncer.update_params(wheel_debounce_params(
+            &config,
+            false,
+            self.has_hi_res,
+        ));
+        self.horizontal_debouncer
+            .update_params(wheel_debounce_params(&config, true, self.has_h_hi_res));
+        self.dial_debouncer
+            .update_params(DebounceParams::for_dial(&config));
+        self.config = config;
+    }
+
+    /// 重新加载按键映射
+    ///
+    /// 虚拟设备创建后不能再增加按键，目标按键不在虚拟设备上的映射会被忽略，
+    /// 需要重启才能生效。
+    fn reload_button_map(&mut self, config: &Config) {
+        // remap_codes 已在加载配置时检查过
+        let button_map = match config.button.remap_codes() {
+            Ok(button_map) => button_map,
+            Err(_) => return,
+        };
+        self.button_map = button_map
+            .into_iter()
+            .filter(|(source, target)| {
+                let enabled = self.enabled_buttons.contains(target);
+                if !enabled {
+                    log_warn!(
+                        "虚拟设备不支持 {:?}，忽略映射 {:?} -> {:?}（需要重启生效）",
+                        target,
+                        source,
+                        target
+                    );
+                }
+                enabled
+            })
+            .collect();
+    }
+
+    /// 重新加载滚轮按键绑定
+    ///
+    /// 与按键映射相同，目标按键不在虚拟设备上的绑定会被忽略，需要重启才能生效。
+    fn reload_wheel_bindings(&mut self, config: &Config) {
+        // wheel_bindings 已在加载配置时检查过
+        let bindings = match config.wheel_bindings() {
+            Ok(bindings) => bindings,
+            Err(_) => return,
+        };
+        self.wheel_bindings = bindings
+            .into_iter()
+            .filter(|binding| {
+                let enabled = self.enabled_buttons.contains(&binding.up)
+                    && self.enabled_buttons.contains(&binding.down);
+                if !enabled {
+                    log_warn!(
+                        "虚拟设备不支持 {:?} 或 {:?}，忽略该滚轮按键绑定（需要重启生效）",
+                        binding.up,
+                        binding.down
+                    );
+                }
+                enabled
+            })
+            .collect();
+    }
+
+    /// 结束运行：处理尚未同步的事件并释放设备
+    fn shutdown(&mut self) -> Result<(), Error> {
+        if !self.pending_events.is_empty() {
+            let time_val = evdev_rs::TimeVal::new(0, 0);
+            let event_code = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+            self.process_event_group(&InputEvent::new(&time_val, &event_code, 0))?;
+        }
+
+        // 处理合并窗口中的滚轮片段，放行预热中的滚动，发送还在等待合并的滚轮值
+        self.flush_wheel_fragments(true)?;
+        self.flush_wheel_warmup(true)?;
+        if self.wheel_flush_at.is_some() {
+            self.wheel_flush_at = Some(Instant::now());
+            self.flush_due_wheel_output()?;
+        }
 
-            // 短暂休眠以减少 CPU 使用率
-            thread::sleep(Duration::from_micros(500));
+        if self.input_device.is_grabbed() {
+            self.input_device.release()?;
+            log_info!("已释放设备");
         }
+        #[cfg(feature = "ipc")]
+        ipc::remove_device(self.input_device.name().unwrap_or("Unknown Mouse"));
+
+        // 过滤比例过高说明消抖参数可能过于激进
+        self.vertical_stats.log_summary("垂直滚轮");
+        self.horizontal_stats.log_summary("水平滚轮");
+        self.dial_stats.log_summary("旋钮");
+        Ok(())
+    }
+
+    /// 设备断开后释放抓取，丢弃未同步的事件
+    fn release_after_disconnect(&mut self) {
+        self.discard_partial_group();
+        // 设备已经不存在时释放抓取会失败，忽略即可
+        let _ = self.input_device.release();
+        #[cfg(feature = "ipc")]
+        ipc::remove_device(self.input_device.name().unwrap_or("Unknown Mouse"));
+    }
+
+    /// 处理一个完整的事件组（`sync` 之前收集的所有事件）
+    ///
+    /// 输出约定：组内所有输出事件（平滑后的滚轮事件和转发的其他事件）都通过 `emit`
+    /// 写入，之后紧跟一个 `sync`；整组都被过滤、没有任何输出时不发送 SYN_REPORT，
+    /// 避免空报告。调用方不需要也不应该再自行写入 SYN_REPORT。
+    fn process_event_group(&mut self, sync: &InputEvent) -> Result<(), Error> {
+        self.group_has_output = false;
+        self.process_pending_events()?;
+        if self.group_has_output {
+            self.write(sync)?;
+        }
+        Ok(())
     }
 
-    fn process_event_group(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// 向虚拟设备写入一个事件组内的事件
+    fn emit(&mut self, event: &InputEvent) -> Result<(), Error> {
+        self.write(event)?;
+        self.group_has_output = true;
+        Ok(())
+    }
+
+    /// 向输出目标写入一个事件
+    fn write(&mut self, event: &InputEvent) -> Result<(), Error> {
+        self.sink.write_event(event)?;
+        metrics::event_forwarded();
+        Ok(())
+    }
+
+    /// 处理收集到的事件组
+    ///
+    /// 垂直和水平滚轮分别累加、用各自的消抖器处理。斜向滚动时同一组里可能同时有
+    /// REL_WHEEL、REL_HWHEEL 和指针移动：两个轴互不影响，某个轴被过滤不会影响另一个轴，
+    /// 指针移动等其他事件总是在原来的位置转发。
+    fn process_pending_events(&mut self) -> Result<(), Error> {
+        self.drop_suppressed_events();
         if self.pending_events.is_empty() {
             return Ok(());
         }
 
+        self.apply_modifiers()?;
+
         // 检查是否有滚轮事件
         let mut has_wheel_events = false;
-        let mut wheel_value = 0;
-        let mut wheel_hi_res_value = 0;
-        let mut hwheel_value = 0;
-        let mut hwheel_hi_res_value = 0;
+        let mut wheel_value: i32 = 0;
+        let mut wheel_hi_res_value: i32 = 0;
+        let mut hwheel_value: i32 = 0;
+        let mut hwheel_hi_res_value: i32 = 0;
+        let mut dial_value: i32 = 0;
 
-        // 优化：一次遍历收集所有滚轮事件值
+        // 优化：一次遍历收集所有滚轮事件值。按住修饰键时垂直滚轮已改为水平滚轮，
+        // 同一组里可能有两个相同代码的事件，这里累加
         for event in &self.pending_events {
             if let EventCode::EV_REL(rel_code) = event.event_code {
                 match rel_code {
                     EV_REL::REL_WHEEL => {
                         has_wheel_events = true;
-                        wheel_value = event.value;
+                        wheel_value = wheel_value.saturating_add(event.value);
                     }
                     EV_REL::REL_WHEEL_HI_RES => {
                         has_wheel_events = true;
-                        wheel_hi_res_value = event.value;
+                        wheel_hi_res_value = wheel_hi_res_value.saturating_add(event.value);
                     }
                     EV_REL::REL_HWHEEL => {
                         has_wheel_events = true;
-                        hwheel_value = event.value;
+                        hwheel_value = hwheel_value.saturating_add(event.value);
                     }
                     EV_REL::REL_HWHEEL_HI_RES => {
                         has_wheel_events = true;
-                        hwheel_hi_res_value = event.value;
+                        hwheel_hi_res_value = hwheel_hi_res_value.saturating_add(event.value);
+                    }
+                    EV_REL::REL_DIAL => {
+                        has_wheel_events = true;
+                        dial_value = dial_value.saturating_add(event.value);
                     }
                     _ => {}
                 }
             }
         }
 
+        // 校准模式统计平滑前的垂直滚轮报告（高分辨率单位）
+        if self.calibrator.is_some() && (wheel_value != 0 || wheel_hi_res_value != 0) {
+            let value = if self.has_hi_res {
+                wheel_hi_res_value
+            } else {
+                wheel_value.saturating_mul(self.config.wheel.hi_res_step)
+            };
+            if let Some(calibrator) = &mut self.calibrator {
+                calibrator.observe(value, Instant::now());
+            }
+        }
+
         // 如果没有滚轮事件或消抖时间为0，直接传递所有事件
         if !has_wheel_events
             || (wheel_value == 0
                 && wheel_hi_res_value == 0
                 && hwheel_value == 0
-                && hwheel_hi_res_value == 0)
-            || (self.config.get_debounce_time() == Duration::from_millis(0)
-                && self.config.get_h_debounce_time() == Duration::from_millis(0))
+                && hwheel_hi_res_value == 0
+                && dial_value == 0)
+            || (!self.config.vertical_debounce_enabled()
+                && self.config.get_h_debounce_time() == Duration::from_millis(0)
+                && self.config.get_dial_debounce_time() == Duration::from_millis(0))
         {
             // 直接传递所有事件
-            for event in &self.pending_events {
-                self.virtual_device.write_event(event)?;
+            let events = std::mem::take(&mut self.pending_events);
+            for event in &events {
+                self.forward_event(event)?;
             }
+            self.flush_pending_scan()?;
+            self.pending_events = events;
             self.pending_events.clear();
             return Ok(());
         }
 
         let now = Instant::now();
+        let vertical_smoothed = self.config.vertical_debounce_enabled();
+        let horizontal_smoothed = !self.config.get_h_debounce_time().is_zero();
+        let dial_smoothed = !self.config.get_dial_debounce_time().is_zero();
 
-        // 处理垂直滚轮
-        if wheel_value != 0 || wheel_hi_res_value != 0 {
-            self.process_vertical_wheel(wheel_value, wheel_hi_res_value, now)?;
-        }
-
-        // 处理水平滚轮
-        if hwheel_value != 0 || hwheel_hi_res_value != 0 {
-            self.process_horizontal_wheel(hwheel_value, hwheel_hi_res_value, now)?;
-        }
-
-        // 处理非滚轮事件
-        for event in &self.pending_events {
-            if let EventCode::EV_REL(rel_code) = event.event_code {
-                match rel_code {
-                    EV_REL::REL_WHEEL
-                    | EV_REL::REL_WHEEL_HI_RES
-                    | EV_REL::REL_HWHEEL
-                    | EV_REL::REL_HWHEEL_HI_RES => {
-                        // 跳过已处理的滚轮事件
-                        continue;
+        // 按内核送达的顺序处理组内事件：每个轴平滑后的滚动在该轴第一个滚轮事件的位置输出，
+        // 同一轴的其他滚轮事件跳过；其他事件（指针移动、按键，以及未启用消抖的滚轮轴的
+        // 原始事件，例如 h_debounce_time_ms = 0 时的水平滚轮）在原来的位置转发，
+        // 按下按键后滚动这样的先后顺序保持不变
+        let mut vertical_done = false;
+        let mut horizontal_done = false;
+        let mut dial_done = false;
+        let events = std::mem::take(&mut self.pending_events);
+        for event in &events {
+            match event.event_code {
+                EventCode::EV_REL(EV_REL::REL_WHEEL | EV_REL::REL_WHEEL_HI_RES)
+                    if vertical_smoothed =>
+                {
+                    if !vertical_done && (wheel_value != 0 || wheel_hi_res_value != 0) {
+                        self.process_vertical_wheel(wheel_value, wheel_hi_res_value, now)?;
                     }
-                    _ => {
-                        // 传递其他相对事件
-                        self.virtual_device.write_event(event)?;
+                    vertical_done = true;
+                }
+                EventCode::EV_REL(EV_REL::REL_HWHEEL | EV_REL::REL_HWHEEL_HI_RES)
+                    if horizontal_smoothed =>
+                {
+                    if !horizontal_done && (hwheel_value != 0 || hwheel_hi_res_value != 0) {
+                        self.process_horizontal_wheel(hwheel_value, hwheel_hi_res_value, now)?;
                     }
+                    horizontal_done = true;
                 }
-            } else {
-                // 传递非相对事件
-                self.virtual_device.write_event(event)?;
+                EventCode::EV_REL(EV_REL::REL_DIAL) if dial_smoothed => {
+                    if !dial_done && dial_value != 0 {
+                        self.process_dial(dial_value)?;
+                    }
+                    dial_done = true;
+                }
+                _ => self.forward_event(event)?,
+            }
+        }
+        self.flush_pending_scan()?;
+
+        // 合并模式下原始滚轮事件已经送达，修正量 = 平滑后的输出 - 原始值
+        if self.merge_mode {
+            let mut subtract = |code, value| {
+                let correction = self.wheel_corrections.entry(code).or_insert(0);
+                *correction = correction.saturating_sub(value);
+            };
+            if vertical_smoothed {
+                subtract(EV_REL::REL_WHEEL, wheel_value);
+                subtract(EV_REL::REL_WHEEL_HI_RES, wheel_hi_res_value);
+            }
+            if horizontal_smoothed {
+                subtract(EV_REL::REL_HWHEEL, hwheel_value);
+                subtract(EV_REL::REL_HWHEEL_HI_RES, hwheel_hi_res_value);
+            }
+            if dial_smoothed {
+                subtract(EV_REL::REL_DIAL, dial_value);
             }
+            self.emit_wheel_corrections()?;
         }
 
-        // 清空待处理事件列表
+        // 清空待处理事件列表（保留已分配的容量）
+        self.pending_events = events;
         self.pending_events.clear();
 
         Ok(())
     }
 
-    // 新增：处理垂直滚轮事件的专用方法
-    fn process_vertical_wheel(
-        &mut self,
-        wheel_value: i32,
-        wheel_hi_res_value: i32,
-        now: Instant,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // 如果消抖时间为0，跳过处理
-        if self.config.get_debounce_time() == Duration::from_millis(0) {
+    /// 处理修饰键：水平滚动修饰键（`button.scroll_modifier`）和滚轮按键绑定的修饰键
+    ///
+    /// 修饰键的按键事件和它前面的扫描码从事件组中移除；水平滚动修饰键按住期间组内的
+    /// 垂直滚轮事件改成对应的水平滚轮事件，之后交给水平滚轮的消抖器处理，绑定的修饰键
+    /// 由 `emit_wheel` 检查。按住期间没有滚动就松开时补发一次按下和松开，保留按键原来的
+    /// 功能。按住状态跨事件组保存。
+    fn apply_modifiers(&mut self) -> Result<(), Error> {
+        // 合并模式下其他程序已经收到了原始事件，修饰键不起作用
+        if self.merge_mode {
+            return Ok(());
+        }
+        let modifiers: Vec<EV_KEY> = self
+            .scroll_modifier
+            .into_iter()
+            .chain(
+                self.wheel_bindings
+                    .iter()
+                    .filter_map(|binding| binding.modifier),
+            )
+            .collect();
+        if modifiers.is_empty() {
             return Ok(());
         }
 
-        // 计算高分辨率值（如果只有标准滚轮事件）
-        let hi_res_value = if wheel_value != 0 && wheel_hi_res_value == 0 {
-            wheel_value * 120
-        } else {
-            wheel_hi_res_value
-        };
-
-        // 应用平滑处理
-        let smoothed_value = self.vertical_debouncer.smooth(hi_res_value, now);
-
-        if smoothed_value != 0 {
-            // 计算标准滚轮事件的值
-            let standard_value = if smoothed_value.abs() >= 120 {
-                smoothed_value / 120
-            } else if smoothed_value > 0 {
-                1
-            } else {
-                -1
+        // 同一组里按下或松开修饰键时，组内的滚轮事件同样视为按住期间的滚动
+        let mut active: HashSet<EV_KEY> = self.held_modifiers.keys().copied().collect();
+        let mut clicks = Vec::new();
+        let events = std::mem::take(&mut self.pending_events);
+        for event in &events {
+            let modifier = match event.event_code {
+                EventCode::EV_KEY(key) if modifiers.contains(&key) => key,
+                _ => {
+                    self.pending_events.push(event.clone());
+                    continue;
+                }
             };
-
-            // 发送标准滚轮事件
-            if standard_value != 0 {
-                let time_val = evdev_rs::TimeVal::new(0, 0);
-                let event_code = EventCode::EV_REL(EV_REL::REL_WHEEL);
-                let wheel_event = InputEvent::new(&time_val, &event_code, standard_value);
-                self.virtual_device.write_event(&wheel_event)?;
+            if matches!(
+                self.pending_events.last(),
+                Some(last) if last.event_code == EventCode::EV_MSC(EV_MSC::MSC_SCAN)
+            ) {
+                self.pending_events.pop();
+            }
+            match event.value {
+                1 => {
+                    self.held_modifiers.insert(modifier, false);
+                    active.insert(modifier);
+                }
+                0 => {
+                    if let Some(used) = self.held_modifiers.remove(&modifier) {
+                        if !used {
+                            clicks.push((modifier, event.time));
+                        }
+                    }
+                }
+                _ => {}
             }
+        }
 
-            // 发送高分辨率滚轮事件
-            let time_val = evdev_rs::TimeVal::new(0, 0);
-            let event_code = EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES);
-            let hi_res_event = InputEvent::new(&time_val, &event_code, smoothed_value);
-            self.virtual_device.write_event(&hi_res_event)?;
+        let has_wheel = self.pending_events.iter().any(|event| {
+            matches!(
+                event.event_code,
+                EventCode::EV_REL(
+                    EV_REL::REL_WHEEL
+                        | EV_REL::REL_WHEEL_HI_RES
+                        | EV_REL::REL_HWHEEL
+                        | EV_REL::REL_HWHEEL_HI_RES
+                )
+            )
+        });
+        if has_wheel {
+            for (modifier, used) in self.held_modifiers.iter_mut() {
+                if active.contains(modifier) {
+                    *used = true;
+                }
+            }
+            clicks.retain(|(modifier, _)| !active.contains(modifier));
+        }
+
+        if self
+            .scroll_modifier
+            .is_some_and(|modifier| active.contains(&modifier))
+        {
+            for event in &mut self.pending_events {
+                let code = match event.event_code {
+                    EventCode::EV_REL(EV_REL::REL_WHEEL) => EV_REL::REL_HWHEEL,
+                    EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES) => EV_REL::REL_HWHEEL_HI_RES,
+                    _ => continue,
+                };
+                event.event_code = EventCode::EV_REL(code);
+            }
+        }
+
+        for (modifier, time) in clicks {
+            log_debug!("{:?} 按住期间没有滚动，补发点击", modifier);
+            let code = EventCode::EV_KEY(modifier);
+            self.forward_button_event(modifier, &InputEvent::new(&time, &code, 1))?;
+            self.forward_button_event(modifier, &InputEvent::new(&time, &code, 0))?;
+        }
+        Ok(())
+    }
+
+    /// 当前生效的滚轮按键绑定，修饰键按住的绑定优先于没有修饰键的绑定
+    fn active_wheel_binding(&self, horizontal: bool) -> Option<WheelBinding> {
+        if self.merge_mode {
+            return None;
+        }
+        let bindings = || {
+            self.wheel_bindings
+                .iter()
+                .filter(move |binding| binding.horizontal == horizontal)
+        };
+        bindings()
+            .find(|binding| {
+                binding
+                    .modifier
+                    .is_some_and(|modifier| self.held_modifiers.contains_key(&modifier))
+            })
+            .or_else(|| bindings().find(|binding| binding.modifier.is_none()))
+            .copied()
+    }
+
+    /// 把滚轮值按刻度转换成绑定的按键，每个刻度发送一次按下和松开
+    ///
+    /// 按下和松开之间插入同步事件，否则同一个报告里的按下和松开可能被应用程序忽略。
+    fn emit_wheel_keys(&mut self, binding: WheelBinding, value: i32) -> Result<(), Error> {
+        let remainder = if binding.horizontal {
+            &mut self.hwheel_remainder
+        } else {
+            &mut self.wheel_remainder
+        };
+        let detents = accumulate_detents(remainder, value, self.config.wheel.hi_res_step);
+        let key = if detents > 0 {
+            binding.up
+        } else {
+            binding.down
+        };
+
+        let time_val = evdev_rs::TimeVal::new(0, 0);
+        let sync = InputEvent::new(&time_val, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0);
+        let code = EventCode::EV_KEY(key);
+        for i in 0..detents.unsigned_abs() {
+            if i > 0 {
+                self.write(&sync)?;
+            }
+            self.emit(&InputEvent::new(&time_val, &code, 1))?;
+            self.write(&sync)?;
+            self.emit(&InputEvent::new(&time_val, &code, 0))?;
+        }
+        Ok(())
+    }
+
+    /// 转发一个非滚轮事件，按键事件经过按键状态跟踪
+    fn forward_event(&mut self, event: &InputEvent) -> Result<(), Error> {
+        // 合并模式下原设备的事件已经送达其他程序
+        if self.merge_mode {
+            return Ok(());
+        }
+
+        match event.event_code {
+            // 扫描码描述的是紧随其后的按键，等按键映射和过滤之后再决定如何发送
+            EventCode::EV_MSC(EV_MSC::MSC_SCAN) => {
+                self.flush_pending_scan()?;
+                self.pending_scan = Some(event.clone());
+                Ok(())
+            }
+            // 鼠标按键经过消抖和映射；触摸板的 BTN_TOUCH、BTN_TOOL_* 等原样转发
+            EventCode::EV_KEY(key)
+                if MOUSE_BUTTONS.contains(&key) || self.button_map.contains_key(&key) =>
+            {
+                self.forward_button_event(key, event)
+            }
+            // 不做消抖的滚轮轴同样按绑定转换成按键，有高分辨率轴时只用高分辨率事件换算
+            EventCode::EV_REL(
+                code @ (EV_REL::REL_WHEEL
+                | EV_REL::REL_WHEEL_HI_RES
+                | EV_REL::REL_HWHEEL
+                | EV_REL::REL_HWHEEL_HI_RES),
+            ) if self.active_wheel_binding(is_horizontal(code)).is_some() => {
+                let horizontal = is_horizontal(code);
+                let (has_hi_res, invert) = if horizontal {
+                    (self.has_h_hi_res, self.config.wheel.invert_horizontal)
+                } else {
+                    (self.has_hi_res, self.config.wheel.invert_vertical)
+                };
+                let value = match code {
+                    EV_REL::REL_WHEEL_HI_RES | EV_REL::REL_HWHEEL_HI_RES => event.value,
+                    _ if has_hi_res => return Ok(()),
+                    _ => event.value.saturating_mul(self.config.wheel.hi_res_step),
+                };
+                let value = if invert {
+                    value.saturating_neg()
+                } else {
+                    value
+                };
+                self.emit_wheel(horizontal, value)
+            }
+            // 不做消抖时同样按配置关闭标准或高分辨率滚轮输出
+            EventCode::EV_REL(EV_REL::REL_WHEEL | EV_REL::REL_HWHEEL)
+                if !self.config.wheel.emit_standard =>
+            {
+                Ok(())
+            }
+            EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES | EV_REL::REL_HWHEEL_HI_RES)
+                if !self.config.wheel.emit_hi_res =>
+            {
+                Ok(())
+            }
+            // 不做消抖时滚轮事件也要按配置反转方向
+            EventCode::EV_REL(code @ (EV_REL::REL_WHEEL | EV_REL::REL_WHEEL_HI_RES))
+                if self.config.wheel.invert_vertical =>
+            {
+                let inverted = InputEvent::new(
+                    &event.time,
+                    &EventCode::EV_REL(code),
+                    event.value.saturating_neg(),
+                );
+                self.emit(&inverted)?;
+                Ok(())
+            }
+            EventCode::EV_REL(code @ (EV_REL::REL_HWHEEL | EV_REL::REL_HWHEEL_HI_RES))
+                if self.config.wheel.invert_horizontal =>
+            {
+                let inverted = InputEvent::new(
+                    &event.time,
+                    &EventCode::EV_REL(code),
+                    event.value.saturating_neg(),
+                );
+                self.emit(&inverted)?;
+                Ok(())
+            }
+            EventCode::EV_REL(EV_REL::REL_DIAL) if self.config.wheel.invert_dial => {
+                let inverted =
+                    InputEvent::new(&event.time, &event.event_code, event.value.saturating_neg());
+                self.emit(&inverted)?;
+                Ok(())
+            }
+            _ => {
+                self.flush_pending_scan()?;
+                self.emit(event)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// 丢弃 `device.suppress_codes` 中的事件，以及被丢弃的鼠标按键对应的 MSC_SCAN
+    ///
+    /// 按物理设备报告的代码匹配，在按键映射、修饰键和滚轮消抖之前进行。
+    fn drop_suppressed_events(&mut self) {
+        if self.suppressed_codes.is_empty() {
+            return;
+        }
+        let suppressed = &self.suppressed_codes;
+        let scancodes: Vec<i32> = self
+            .pending_events
+            .iter()
+            .filter_map(|event| match event.event_code {
+                EventCode::EV_KEY(key) if suppressed.contains(&event.event_code) => {
+                    button_scancode(key)
+                }
+                _ => None,
+            })
+            .collect();
+        self.pending_events.retain(|event| match event.event_code {
+            EventCode::EV_MSC(EV_MSC::MSC_SCAN) => !scancodes.contains(&event.value),
+            code => !suppressed.contains(&code),
+        });
+    }
+
+    /// 原样发送后面没有跟着鼠标按键事件的 MSC_SCAN
+    fn flush_pending_scan(&mut self) -> Result<(), Error> {
+        if let Some(scan) = self.pending_scan.take() {
+            self.emit(&scan)?;
+        }
+        Ok(())
+    }
+
+    /// 获取物理按键对应的虚拟按键
+    ///
+    /// `button.remap` 中的映射优先，其次是左手模式的左右键交换，其余按键原样返回。
+    fn map_button(&self, source: EV_KEY) -> EV_KEY {
+        if let Some(&target) = self.button_map.get(&source) {
+            return target;
+        }
+        match source {
+            EV_KEY::BTN_LEFT if self.config.device.left_handed => EV_KEY::BTN_RIGHT,
+            EV_KEY::BTN_RIGHT if self.config.device.left_handed => EV_KEY::BTN_LEFT,
+            _ => source,
+        }
+    }
+
+    /// 转发按键事件
+    ///
+    /// 多个物理按键可能映射到同一个虚拟按键，这里按虚拟按键做引用计数：
+    /// 只有第一个来源按下时才发送按下事件，所有来源都松开后才发送松开事件，
+    /// 避免虚拟按键被重复按下或提前松开。
+    fn forward_button_event(&mut self, source: EV_KEY, event: &InputEvent) -> Result<(), Error> {
+        // 按键前面的扫描码随按键一起发送，按键被过滤时一并丢弃
+        let scan = self.pending_scan.take();
+
+        // 过滤微动开关抖动产生的多余点击
+        if !self
+            .button_debouncer
+            .accept(source, event.value, Instant::now())
+        {
+            return Ok(());
+        }
+
+        // 松开和自动重复使用按下时的映射，映射在按住期间被重新加载也能正确配对
+        let target = match self.pressed_buttons.get(&source) {
+            Some(&target) => target,
+            None => self.map_button(source),
+        };
+
+        match event.value {
+            1 => {
+                // 同一来源重复按下，忽略
+                if self.pressed_buttons.insert(source, target).is_some() {
+                    return Ok(());
+                }
+                let count = self.button_refcounts.entry(target).or_insert(0);
+                *count += 1;
+                if *count > 1 {
+                    log_debug!(
+                        "{:?} 已被其他按键按下，忽略来自 {:?} 的按下事件",
+                        target,
+                        source
+                    );
+                    return Ok(());
+                }
+            }
+            0 => {
+                // 没有对应按下的松开事件，忽略
+                if self.pressed_buttons.remove(&source).is_none() {
+                    return Ok(());
+                }
+                if let Some(count) = self.button_refcounts.get_mut(&target) {
+                    *count = count.saturating_sub(1);
+                    if *count > 0 {
+                        log_debug!("{:?} 仍被其他按键按住，暂不松开", target);
+                        return Ok(());
+                    }
+                }
+                self.button_refcounts.remove(&target);
+            }
+            _ => {
+                // 自动重复事件，只在来源确实按下时转发
+                if !self.pressed_buttons.contains_key(&source) {
+                    return Ok(());
+                }
+            }
+        }
+
+        // 未映射的按键保留原扫描码；映射后的按键改为目标按键的 HID 扫描码，
+        // 目标不是鼠标按键时没有对应的扫描码，不发送
+        if let Some(scan) = scan {
+            if target == source {
+                self.emit(&scan)?;
+            } else if let Some(code) = button_scancode(target) {
+                self.emit(&InputEvent::new(&scan.time, &scan.event_code, code))?;
+            }
+        }
+
+        let event_code = EventCode::EV_KEY(target);
+        let key_event = InputEvent::new(&event.time, &event_code, event.value);
+        self.emit(&key_event)?;
+        Ok(())
+    }
+
+    /// 把估计的回报率交给消抖器，换算 EMA 每个事件的权重；未启用归一化时按参考回报率处理
+    fn apply_polling_rate(&mut self) {
+        let periods = if self.config.wheel.normalize_by_polling_rate {
+            self.polling_rate
+                .report_periods(self.config.wheel.polling_reference_hz as f64)
+        } else {
+            1.0
+        };
+        self.vertical_debouncer.set_report_periods(periods);
+        self.horizontal_debouncer.set_report_periods(periods);
+        self.dial_debouncer.set_report_periods(periods);
+    }
+
+    /// 加速产生的额外倍数
+    ///
+    /// 与上一次滚动的间隔小于 `ACCELERATION_WINDOW` 时按间隔线性增加：
+    /// 间隔越短倍数越大，最大为 `acceleration_max`。启用回报率归一化时间隔先换算到
+    /// 参考回报率，高回报率设备拆出的密集片段不会触发更强的加速。
+    fn acceleration_factor(&self, interval: Duration) -> f64 {
+        let acceleration = self.config.wheel.acceleration;
+        let interval = if self.config.wheel.normalize_by_polling_rate {
+            self.polling_rate
+                .normalize_interval(interval, self.config.wheel.polling_reference_hz as f64)
+        } else {
+            interval
+        };
+        if acceleration <= 0.0 || interval >= ACCELERATION_WINDOW {
+            return 1.0;
+        }
+        let speed = 1.0 - interval.as_secs_f64() / ACCELERATION_WINDOW.as_secs_f64();
+        (1.0 + acceleration * speed).min(self.config.wheel.acceleration_max)
+    }
+
+    /// 平滑后滚动值的总缩放系数：倍数 × 加速
+    fn output_scale(&self, interval: Duration) -> f64 {
+        self.config.wheel.multiplier * self.acceleration_factor(interval)
+    }
+
+    /// 输出一个平滑后的滚轮值
+    ///
+    /// 设置了 `output_interval_ms` 时先累积，距上次输出满一个间隔才合并成一个事件发送；
+    /// 未满间隔时记下发送时间，由 `run` 到时发送，不必等下一个输入事件。
+    fn output_wheel(&mut self, horizontal: bool, value: i32, now: Instant) -> Result<(), Error> {
+        let interval = self.config.get_output_interval();
+        if interval.is_zero() {
+            return self.emit_wheel(horizontal, value);
+        }
+
+        if horizontal {
+            self.hwheel_output_pending = self.hwheel_output_pending.saturating_add(value);
+        } else {
+            self.wheel_output_pending = self.wheel_output_pending.saturating_add(value);
+        }
+
+        let due = self.last_wheel_flush + interval;
+        if now >= due {
+            self.flush_wheel_output(now)
+        } else {
+            self.wheel_flush_at = Some(due);
+            Ok(())
+        }
+    }
+
+    /// 发送累积的滚轮值
+    fn flush_wheel_output(&mut self, now: Instant) -> Result<(), Error> {
+        self.wheel_flush_at = None;
+        self.last_wheel_flush = now;
+
+        let value = std::mem::take(&mut self.wheel_output_pending);
+        if value != 0 {
+            self.emit_wheel(false, value)?;
+        }
+        let value = std::mem::take(&mut self.hwheel_output_pending);
+        if value != 0 {
+            self.emit_wheel(true, value)?;
+        }
+        Ok(())
+    }
+
+    /// 到达发送时间时在事件组之外发送累积的滚轮值，并补上同步事件
+    fn flush_due_wheel_output(&mut self) -> Result<(), Error> {
+        let now = Instant::now();
+        match self.wheel_flush_at {
+            Some(due) if now >= due => {}
+            _ => return Ok(()),
+        }
+
+        self.group_has_output = false;
+        self.flush_wheel_output(now)?;
+        self.emit_wheel_corrections()?;
+        if self.group_has_output {
+            let time_val = evdev_rs::TimeVal::new(0, 0);
+            let event_code = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+            self.write(&InputEvent::new(&time_val, &event_code, 0))?;
+        }
+        Ok(())
+    }
+
+    /// 合并窗口结束时在事件组之外对缓冲的滚轮片段消抖并输出，并补上同步事件
+    ///
+    /// `all` 为 true 时不等窗口结束，处理所有缓冲的片段。
+    fn flush_wheel_fragments(&mut self, all: bool) -> Result<(), Error> {
+        let now = Instant::now();
+        let (vertical, horizontal) = if all {
+            (self.wheel_fragments.take(), self.hwheel_fragments.take())
+        } else {
+            (
+                self.wheel_fragments.take_due(now),
+                self.hwheel_fragments.take_due(now),
+            )
+        };
+        if vertical.is_none() && horizontal.is_none() {
+            return Ok(());
+        }
+
+        self.group_has_output = false;
+        if let Some(value) = vertical {
+            self.smooth_vertical_wheel(value, now)?;
+        }
+        if let Some(value) = horizontal {
+            self.smooth_horizontal_wheel(value, now)?;
+        }
+        self.emit_wheel_corrections()?;
+        if self.group_has_output {
+            let time_val = evdev_rs::TimeVal::new(0, 0);
+            let event_code = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+            self.write(&InputEvent::new(&time_val, &event_code, 0))?;
+        }
+        Ok(())
+    }
+
+    /// 预热到期时在事件组之外放行消抖器缓冲的滚动，并补上同步事件
+    ///
+    /// 只滚动一格时凑不满预热事件数，缓冲的滚动要等到期后才发送。
+    /// `all` 为 true 时不等到期，放行所有缓冲的滚动。
+    fn flush_wheel_warmup(&mut self, all: bool) -> Result<(), Error> {
+        let take = |debouncer: &mut WheelDebouncer| {
+            if all {
+                debouncer.take_warmup()
+            } else {
+                debouncer.take_warmup_due()
+            }
+        };
+        let vertical = take(&mut self.vertical_debouncer);
+        let horizontal = take(&mut self.horizontal_debouncer);
+        let dial = take(&mut self.dial_debouncer);
+        if vertical.is_none() && horizontal.is_none() && dial.is_none() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        self.group_has_output = false;
+        if let Some(value) = vertical {
+            self.output_smoothed_vertical(value, now)?;
+        }
+        if let Some(value) = horizontal {
+            self.output_smoothed_horizontal(value, now)?;
+        }
+        if let Some(value) = dial {
+            self.output_smoothed_dial(value)?;
+        }
+        self.emit_wheel_corrections()?;
+        if self.group_has_output {
+            let time_val = evdev_rs::TimeVal::new(0, 0);
+            let event_code = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+            self.write(&InputEvent::new(&time_val, &event_code, 0))?;
+        }
+        Ok(())
+    }
+
+    /// 合并模式下发送累积的滚轮修正量
+    fn emit_wheel_corrections(&mut self) -> Result<(), Error> {
+        let time_val = evdev_rs::TimeVal::new(0, 0);
+        for code in [
+            EV_REL::REL_WHEEL,
+            EV_REL::REL_WHEEL_HI_RES,
+            EV_REL::REL_HWHEEL,
+            EV_REL::REL_HWHEEL_HI_RES,
+            EV_REL::REL_DIAL,
+        ] {
+            let value = self.wheel_corrections.remove(&code).unwrap_or(0);
+            if value != 0 {
+                self.emit(&InputEvent::new(&time_val, &EventCode::EV_REL(code), value))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 发送一个滚轮值的标准事件和高分辨率事件
+    ///
+    /// 开启 `hi_res_whole_detents` 时两者都只在凑满整刻度时发送，高分辨率值等于刻度数乘以步长。
+    /// 该轴上有生效的按键绑定时改为发送按键。
+    fn emit_wheel(&mut self, horizontal: bool, value: i32) -> Result<(), Error> {
+        if let Some(binding) = self.active_wheel_binding(horizontal) {
+            return self.emit_wheel_keys(binding, value);
+        }
 
-            self.last_event_time = now;
-            self.last_wheel_time = now;
-            self.last_wheel_value = smoothed_value;
+        let (remainder, standard_carry, hi_res_carry, standard_code, hi_res_code) = if horizontal {
+            (
+                &mut self.hwheel_remainder,
+                &mut self.hwheel_standard_carry,
+                &mut self.hwheel_hi_res_carry,
+                EV_REL::REL_HWHEEL,
+                EV_REL::REL_HWHEEL_HI_RES,
+            )
+        } else {
+            (
+                &mut self.wheel_remainder,
+                &mut self.wheel_standard_carry,
+                &mut self.wheel_hi_res_carry,
+                EV_REL::REL_WHEEL,
+                EV_REL::REL_WHEEL_HI_RES,
+            )
+        };
+        let wheel = &self.config.wheel;
+
+        // 计算标准滚轮事件的值
+        // 高分辨率值累积满一个刻度才发送标准事件，余量留到下次
+        let detents = accumulate_detents(remainder, value, wheel.hi_res_step);
+        // 两种事件分别缩放，小数部分各自累积，长时间滚动后两者的距离都不会漂移
+        let standard_value = scale_with_carry(detents, wheel.standard_scale, standard_carry);
+        // 只按整刻度发送时，高分辨率值按刻度数缩放后再换算，余量与标准事件一起留到下次
+        let hi_res_value = if wheel.hi_res_whole_detents {
+            scale_with_carry(detents, wheel.hi_res_scale, hi_res_carry)
+                .saturating_mul(wheel.hi_res_step)
+        } else {
+            scale_with_carry(value, wheel.hi_res_scale, hi_res_carry)
+        };
+
+        // 合并模式下先记入修正量，在事件组结束时减去原始值再发送
+        if self.merge_mode {
+            if self.config.wheel.emit_standard {
+                let correction = self.wheel_corrections.entry(standard_code).or_insert(0);
+                *correction = correction.saturating_add(standard_value);
+            }
+            if self.config.wheel.emit_hi_res {
+                let correction = self.wheel_corrections.entry(hi_res_code).or_insert(0);
+                *correction = correction.saturating_add(hi_res_value);
+            }
+            return Ok(());
+        }
+
+        let time_val = evdev_rs::TimeVal::new(0, 0);
+
+        // 发送标准滚轮事件
+        if standard_value != 0 && self.config.wheel.emit_standard {
+            let event_code = EventCode::EV_REL(standard_code);
+            self.emit(&InputEvent::new(&time_val, &event_code, standard_value))?;
+        }
+
+        // 发送高分辨率滚轮事件
+        if hi_res_value != 0 && self.config.wheel.emit_hi_res {
+            let event_code = EventCode::EV_REL(hi_res_code);
+            self.emit(&InputEvent::new(&time_val, &event_code, hi_res_value))?;
+        }
+        Ok(())
+    }
+
+    // 新增：处理垂直滚轮事件的专用方法
+    fn process_vertical_wheel(
+        &mut self,
+        wheel_value: i32,
+        wheel_hi_res_value: i32,
+        now: Instant,
+    ) -> Result<(), Error> {
+        // 如果消抖时间为0，跳过处理，原始事件由 process_event_group 原样转发
+        if !self.config.vertical_debounce_enabled() {
+            return Ok(());
+        }
+
+        // 没有高分辨率滚轮的设备对刻度数消抖
+        let value = if self.has_hi_res {
+            wheel_hi_res_value
+        } else {
+            wheel_value
+        };
+
+        // 合并窗口内的高分辨率片段先缓冲，只有标准滚轮的设备每次报告都是整刻度，不需要合并
+        let window = self.config.get_merge_window();
+        if !self.has_hi_res || window.is_zero() {
+            return self.smooth_vertical_wheel(value, now);
+        }
+        let step = self.config.wheel.hi_res_step;
+        for value in self
+            .wheel_fragments
+            .push(value, now, window, step)
+            .into_iter()
+            .flatten()
+        {
+            self.smooth_vertical_wheel(value, now)?;
+        }
+        Ok(())
+    }
+
+    /// 对一个垂直滚轮值（有高分辨率轴时为高分辨率单位，否则为刻度数）消抖并输出
+    fn smooth_vertical_wheel(&mut self, value: i32, now: Instant) -> Result<(), Error> {
+        // 应用平滑处理
+        let smoothed_value = self.vertical_debouncer.smooth(value);
+        metrics::scroll_report(self.vertical_debouncer.last_interval());
+
+        if smoothed_value != 0 {
+            self.output_smoothed_vertical(smoothed_value, now)?;
+        } else if self.vertical_debouncer.deferred() {
+            log_debug!("  [已推迟] 滚动值在死区内或正在预热，累积到下一次");
         } else {
             log_info!("  [已过滤] 可能是抖动");
+            metrics::jitter_filtered(false);
+            self.vertical_stats.filtered += 1;
         }
 
         Ok(())
     }
 
+    /// 输出一个消抖后的垂直滚轮值：换算到高分辨率单位、缩放、按配置反转方向后发送
+    fn output_smoothed_vertical(&mut self, smoothed_value: i32, now: Instant) -> Result<(), Error> {
+        self.vertical_stats.passed += 1;
+        // 之后的缩放和输出都以高分辨率单位进行
+        let smoothed_value = if self.has_hi_res {
+            smoothed_value
+        } else {
+            smoothed_value.saturating_mul(self.config.wheel.hi_res_step)
+        };
+        // 缩放后的小数部分累积到下一次，不会因取整丢失距离
+        let scale = self.output_scale(self.vertical_debouncer.last_interval());
+        let smoothed_value = if scale != 1.0 {
+            scale_with_carry(smoothed_value, scale, &mut self.wheel_scale_carry)
+        } else {
+            smoothed_value
+        };
+        if smoothed_value == 0 {
+            return Ok(());
+        }
+
+        // 消抖器看到的是原始方向，反转只作用于输出
+        let smoothed_value = if self.config.wheel.invert_vertical {
+            smoothed_value.saturating_neg()
+        } else {
+            smoothed_value
+        };
+
+        self.output_wheel(false, smoothed_value, now)
+    }
+
     // 新增：处理水平滚轮事件的专用方法
     fn process_horizontal_wheel(
         &mut self,
         hwheel_value: i32,
         hwheel_hi_res_value: i32,
         now: Instant,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // 如果消抖时间为0，跳过处理
+    ) -> Result<(), Error> {
+        // 如果消抖时间为0，跳过处理，原始事件由 process_event_group 原样转发
         if self.config.get_h_debounce_time() == Duration::from_millis(0) {
             return Ok(());
         }
 
-        // 计算高分辨率值（如果只有标准水平滚轮事件）
-        let hi_res_value = if hwheel_value != 0 && hwheel_hi_res_value == 0 {
-            hwheel_value * 120
-        } else {
+        // 没有高分辨率水平滚轮的设备对刻度数消抖
+        let value = if self.has_h_hi_res {
             hwheel_hi_res_value
+        } else {
+            hwheel_value
         };
 
+        // 倾斜滚轮的连续重复先限流。按住水平滚动修饰键时的水平滚动来自垂直滚轮，不限制
+        let modifier_held = self
+            .scroll_modifier
+            .is_some_and(|modifier| self.held_modifiers.contains_key(&modifier));
+        let interval = self.config.get_hwheel_repeat_interval();
+        if !modifier_held && !self.hwheel_repeat.accept(value, now, interval) {
+            log_debug!(
+                "  [已限流] 倾斜滚轮重复间隔 {:?} 内的同方向水平滚动",
+                interval
+            );
+            return Ok(());
+        }
+
+        // 合并窗口内的高分辨率片段先缓冲，只有标准滚轮的设备每次报告都是整刻度，不需要合并
+        let window = self.config.get_merge_window();
+        if !self.has_h_hi_res || window.is_zero() {
+            return self.smooth_horizontal_wheel(value, now);
+        }
+        let step = self.config.wheel.hi_res_step;
+        for value in self
+            .hwheel_fragments
+            .push(value, now, window, step)
+            .into_iter()
+            .flatten()
+        {
+            self.smooth_horizontal_wheel(value, now)?;
+        }
+        Ok(())
+    }
+
+    /// 对一个水平滚轮值（有高分辨率轴时为高分辨率单位，否则为刻度数）消抖并输出
+    fn smooth_horizontal_wheel(&mut self, value: i32, now: Instant) -> Result<(), Error> {
         // 应用平滑处理
-        let smoothed_value = self.horizontal_debouncer.smooth(hi_res_value, now);
+        let smoothed_value = self.horizontal_debouncer.smooth(value);
+        metrics::scroll_report(self.horizontal_debouncer.last_interval());
 
         if smoothed_value != 0 {
-            // 计算标准水平滚轮事件的值
-            let standard_value = if smoothed_value.abs() >= 120 {
-                smoothed_value / 120
-            } else if smoothed_value > 0 {
-                1
-            } else {
-                -1
-            };
+            self.output_smoothed_horizontal(smoothed_value, now)?;
+        } else if self.horizontal_debouncer.deferred() {
+            log_debug!("  [已推迟] 滚动值在死区内或正在预热，累积到下一次");
+        } else {
+            log_info!("  [已过滤] 可能是水平滚轮抖动");
+            metrics::jitter_filtered(true);
+            self.horizontal_stats.filtered += 1;
+        }
 
-            // 发送标准水平滚轮事件
-            if standard_value != 0 {
-                let time_val = evdev_rs::TimeVal::new(0, 0);
-                let event_code = EventCode::EV_REL(EV_REL::REL_HWHEEL);
-                let wheel_event = InputEvent::new(&time_val, &event_code, standard_value);
-                self.virtual_device.write_event(&wheel_event)?;
-            }
+        Ok(())
+    }
 
-            // 发送高分辨率水平滚轮事件
-            let time_val = evdev_rs::TimeVal::new(0, 0);
-            let event_code = EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES);
-            let hi_res_event = InputEvent::new(&time_val, &event_code, smoothed_value);
-            self.virtual_device.write_event(&hi_res_event)?;
+    /// 输出一个消抖后的水平滚轮值：换算到高分辨率单位、缩放、按配置反转方向后发送
+    fn output_smoothed_horizontal(
+        &mut self,
+        smoothed_value: i32,
+        now: Instant,
+    ) -> Result<(), Error> {
+        self.horizontal_stats.passed += 1;
+        // 之后的缩放和输出都以高分辨率单位进行
+        let smoothed_value = if self.has_h_hi_res {
+            smoothed_value
+        } else {
+            smoothed_value.saturating_mul(self.config.wheel.hi_res_step)
+        };
+        // 缩放后的小数部分累积到下一次，不会因取整丢失距离
+        let scale = self.output_scale(self.horizontal_debouncer.last_interval());
+        let smoothed_value = if scale != 1.0 {
+            scale_with_carry(smoothed_value, scale, &mut self.hwheel_scale_carry)
+        } else {
+            smoothed_value
+        };
+        if smoothed_value == 0 {
+            return Ok(());
+        }
 
-            self.last_event_time = now;
-            self.last_hwheel_time = now;
-            self.last_hwheel_value = smoothed_value;
+        // 消抖器看到的是原始方向，反转只作用于输出
+        let smoothed_value = if self.config.wheel.invert_horizontal {
+            smoothed_value.saturating_neg()
         } else {
-            log_info!("  [已过滤] 可能是水平滚轮抖动");
+            smoothed_value
+        };
+
+        self.output_wheel(true, smoothed_value, now)
+    }
+
+    /// 对旋钮（REL_DIAL）的值消抖并输出
+    ///
+    /// 旋钮没有高分辨率轴，直接对格数消抖；平滑后的值不经过倍数、加速、输出合并和
+    /// 滚轮按键绑定，只按配置反转方向后发送。
+    fn process_dial(&mut self, value: i32) -> Result<(), Error> {
+        let smoothed_value = self.dial_debouncer.smooth(value);
+
+        if smoothed_value != 0 {
+            self.output_smoothed_dial(smoothed_value)?;
+        } else if self.dial_debouncer.deferred() {
+            log_debug!("  [已推迟] 旋钮值在死区内或正在预热，累积到下一次");
+        } else {
+            log_info!("  [已过滤] 可能是旋钮抖动");
+            self.dial_stats.filtered += 1;
         }
 
         Ok(())
     }
+
+    /// 输出一个消抖后的旋钮值，按配置反转方向
+    fn output_smoothed_dial(&mut self, smoothed_value: i32) -> Result<(), Error> {
+        self.dial_stats.passed += 1;
+        let smoothed_value = if self.config.wheel.invert_dial {
+            smoothed_value.saturating_neg()
+        } else {
+            smoothed_value
+        };
+
+        // 合并模式下先记入修正量，在事件组结束时减去原始值再发送
+        if self.merge_mode {
+            let correction = self.wheel_corrections.entry(EV_REL::REL_DIAL).or_insert(0);
+            *correction = correction.saturating_add(smoothed_value);
+            return Ok(());
+        }
+
+        let time_val = evdev_rs::TimeVal::new(0, 0);
+        let event_code = EventCode::EV_REL(EV_REL::REL_DIAL);
+        self.emit(&InputEvent::new(&time_val, &event_code, smoothed_value))
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 检查是否有足够的权限
-    if !is_root() {
-        log_error!("错误: 需要 root 权限来访问输入设备");
-        log_error!("请使用 sudo 运行此程序");
-        return Err("需要 root 权限".into());
+/// 鼠标按键的 HID 扫描码：Button 页（0x09）中的序号，BTN_LEFT 为 0x90001，
+/// 依次递增到 BTN_TASK 的 0x90008，与 USB 鼠标上报的扫描码一致。其他按键返回 None
+fn button_scancode(key: EV_KEY) -> Option<i32> {
+    MOUSE_BUTTONS
+        .iter()
+        .position(|&button| button == key)
+        .map(|index| 0x90001 + index as i32)
+}
+
+/// 是否是水平滚轮的相对轴
+fn is_horizontal(code: EV_REL) -> bool {
+    matches!(code, EV_REL::REL_HWHEEL | EV_REL::REL_HWHEEL_HI_RES)
+}
+
+/// 虚拟设备支持的按键：默认的鼠标按键，以及按键映射和滚轮按键绑定的目标按键
+fn virtual_buttons(
+    button_map: &HashMap<EV_KEY, EV_KEY>,
+    wheel_bindings: &[WheelBinding],
+) -> HashSet<EV_KEY> {
+    MOUSE_BUTTONS
+        .iter()
+        .chain(button_map.values())
+        .copied()
+        .chain(
+            wheel_bindings
+                .iter()
+                .flat_map(|binding| [binding.up, binding.down]),
+        )
+        .collect()
+}
+
+/// 滚轮消抖参数，没有高分辨率滚轮的轴换算为刻度数
+fn wheel_debounce_params(config: &Config, horizontal: bool, hi_res: bool) -> DebounceParams {
+    let params = DebounceParams::from_config(config, horizontal);
+    if hi_res {
+        params
+    } else {
+        params.in_detents(config.wheel.hi_res_step)
+    }
+}
+
+/// 读取选项后面的毫秒数参数
+fn millis_arg(args: &[String], i: usize) -> Result<u64, Error> {
+    let value = match args.get(i + 1) {
+        Some(value) => value,
+        None => {
+            log_error!(
+                "{}",
+                tr!(
+                    "错误: {} 选项需要一个参数",
+                    "Error: option {} requires an argument",
+                    args[i]
+                )
+            );
+            print_usage();
+            return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
+        }
+    };
+    match value.parse::<u64>() {
+        Ok(millis) => Ok(millis),
+        Err(_) => {
+            log_error!(
+                "{}",
+                tr!(
+                    "错误: {} 的参数必须是毫秒数: '{}'",
+                    "Error: {} expects a number of milliseconds: '{}'",
+                    args[i],
+                    value
+                )
+            );
+            Err(Error::InvalidArgument(tr!("无效参数", "invalid argument")))
+        }
     }
+}
+
+/// 等待设备线程结束，失败时记录设备路径
+fn join_device_thread(
+    path: String,
+    handle: thread::JoinHandle<Result<(), Error>>,
+    failed: &mut Vec<String>,
+) {
+    match handle.join() {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            log_error!("设备 {} 处理失败: {}", path, e);
+            failed.push(path);
+        }
+        Err(_) => {
+            log_error!("设备 {} 的处理线程异常退出", path);
+            failed.push(path);
+        }
+    }
+}
+
+/// 创建虚拟设备并输出其设备节点和 sysfs 路径，便于编写 udev 规则
+fn create_virtual_device<D: DeviceWrapper>(device: &D) -> Result<UInputDevice, Error> {
+    let virtual_device = UInputDevice::create_from_device(device)
+        .map_err(|e| device_error("无法创建虚拟设备", e))?;
+    log_info!(
+        "虚拟设备节点: {}，sysfs 路径: {}",
+        virtual_device.devnode().unwrap_or("未知"),
+        virtual_device.syspath().unwrap_or("未知")
+    );
+    Ok(virtual_device)
+}
+
+/// 设置了 `debug.record_path` 时把输出目标包装成同时录制输出事件的 `RecordingSink`，
+/// 录制文件中的设备描述取自 `device`
+fn with_recording<D: DeviceWrapper>(
+    sink: Box<dyn EventSink>,
+    device: &D,
+    record_path: Option<&str>,
+) -> Result<Box<dyn EventSink>, Error> {
+    match record_path {
+        Some(path) => match RecordingSink::create(path, device, sink) {
+            Ok(sink) => Ok(Box::new(sink)),
+            Err(e) => Err(std::io::Error::new(
+                e.kind(),
+                format!("无法创建录制文件 {}: {}", path, e),
+            )
+            .into()),
+        },
+        None => Ok(sink),
+    }
+}
+
+/// 声明虚拟设备需要的按键、相对轴和杂项事件
+fn enable_virtual_codes<D: DeviceWrapper>(
+    device: &D,
+    buttons: &HashSet<EV_KEY>,
+    dial: bool,
+    config: &Config,
+) -> std::io::Result<()> {
+    for button in buttons {
+        device.enable_event_code(&EventCode::EV_KEY(*button), None)?;
+    }
+
+    // 添加相对轴支持。触摸板使用绝对坐标，声明 REL_X/REL_Y 会被识别成鼠标
+    if !is_touchpad(device) {
+        device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_X), None)?;
+        device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_Y), None)?;
+    }
+    device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_WHEEL), None)?;
+    // 添加水平滚轮支持
+    device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_HWHEEL), None)?;
+    // 只输出标准滚轮事件时不声明高分辨率轴，否则 libinput 会忽略标准事件
+    if config.wheel.emit_hi_res {
+        device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES), None)?;
+        device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES), None)?;
+    }
+    // 原设备有旋钮时同样声明，否则写入的 REL_DIAL 会被内核丢弃
+    if dial {
+        device.enable_event_code(&EventCode::EV_REL(EV_REL::REL_DIAL), None)?;
+    }
+
+    // 添加杂项事件支持
+    device.enable_event_code(&EventCode::EV_MSC(EV_MSC::MSC_SCAN), None)?;
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    // 帮助信息和参数错误按环境变量选择语言，加载配置后再按 logging.language 设置
+    i18n::set_language(i18n::Language::from_env());
 
     // 解析命令行参数
     let args: Vec<String> = env::args().collect();
     let mut list_only = false;
+    let mut verbose = false;
+    let mut smooth_all = false;
     let mut specified_device: Option<String> = None;
-    let mut config_path = String::from("/etc/mouse_smoother.toml");
+    let mut config_path: Option<String> = None;
     let mut create_config = false;
+    let mut annotated = false;
     let mut cmd_log_level: Option<String> = None;
+    let mut quiet = false;
+    let mut run_for: Option<Duration> = None;
+    let mut overrides = ConfigOverrides::default();
+    let mut dump_events = false;
+    let mut simulate_file: Option<String> = None;
+    let mut show_config = false;
+    let mut benchmark_mode = false;
+    let mut calibrate_for: Option<Duration> = None;
+    let mut save_calibration = false;
+    let mut daemon_mode = false;
+    let mut pid_file = String::from("/run/mouse_smoother.pid");
 
     let mut i = 1;
     while i < args.len() {
@@ -373,63 +2233,384 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 list_only = true;
                 i += 1;
             }
+            "--verbose" => {
+                verbose = true;
+                i += 1;
+            }
+            "-a" | "--all" => {
+                smooth_all = true;
+                i += 1;
+            }
             "-d" | "--device" => {
                 if i + 1 < args.len() {
                     specified_device = Some(args[i + 1].clone());
                     i += 2;
                 } else {
-                    log_error!("错误: --device 选项需要一个参数");
+                    log_error!(
+                        "{}",
+                        tr!(
+                            "错误: --device 选项需要一个参数",
+                            "Error: option --device requires an argument"
+                        )
+                    );
                     print_usage();
-                    return Err("缺少参数".into());
+                    return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
                 }
             }
             "-c" | "--config" => {
                 if i + 1 < args.len() {
-                    config_path = args[i + 1].clone();
+                    config_path = Some(args[i + 1].clone());
                     i += 2;
                 } else {
-                    log_error!("错误: --config 选项需要一个参数");
+                    log_error!(
+                        "{}",
+                        tr!(
+                            "错误: --config 选项需要一个参数",
+                            "Error: option --config requires an argument"
+                        )
+                    );
                     print_usage();
-                    return Err("缺少参数".into());
+                    return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
                 }
             }
             "--create-config" => {
                 create_config = true;
                 i += 1;
             }
+            "--annotated" => {
+                annotated = true;
+                i += 1;
+            }
             "--log-level" => {
                 if i + 1 < args.len() {
                     cmd_log_level = Some(args[i + 1].clone());
                     i += 2;
                 } else {
-                    log_error!("错误: --log-level 选项需要一个参数");
+                    log_error!(
+                        "{}",
+                        tr!(
+                            "错误: --log-level 选项需要一个参数",
+                            "Error: option --log-level requires an argument"
+                        )
+                    );
+                    print_usage();
+                    return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
+                }
+            }
+            "-q" | "--quiet" => {
+                quiet = true;
+                cmd_log_level = Some(String::from("error"));
+                i += 1;
+            }
+            "--run-for" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<u64>() {
+                        Ok(secs) => run_for = Some(Duration::from_secs(secs)),
+                        Err(_) => {
+                            log_error!(
+                                "{}",
+                                tr!(
+                                    "错误: --run-for 的参数必须是秒数: '{}'",
+                                    "Error: --run-for expects a number of seconds: '{}'",
+                                    args[i + 1]
+                                )
+                            );
+                            return Err(Error::InvalidArgument(tr!(
+                                "无效参数",
+                                "invalid argument"
+                            )));
+                        }
+                    }
+                    i += 2;
+                } else {
+                    log_error!(
+                        "{}",
+                        tr!(
+                            "错误: --run-for 选项需要一个参数",
+                            "Error: option --run-for requires an argument"
+                        )
+                    );
+                    print_usage();
+                    return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
+                }
+            }
+            "--dump-events" => {
+                dump_events = true;
+                i += 1;
+            }
+            "--simulate" => {
+                if i + 1 < args.len() {
+                    simulate_file = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    log_error!(
+                        "{}",
+                        tr!(
+                            "错误: --simulate 选项需要一个参数",
+                            "Error: option --simulate requires an argument"
+                        )
+                    );
+                    print_usage();
+                    return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
+                }
+            }
+            "--benchmark" => {
+                benchmark_mode = true;
+                i += 1;
+            }
+            "--calibrate" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<u64>() {
+                        Ok(secs) if secs > 0 => calibrate_for = Some(Duration::from_secs(secs)),
+                        _ => {
+                            log_error!(
+                                "{}",
+                                tr!(
+                                    "错误: --calibrate 的参数必须是正整数秒数: '{}'",
+                                    "Error: --calibrate expects a positive number of seconds: '{}'",
+                                    args[i + 1]
+                                )
+                            );
+                            return Err(Error::InvalidArgument(tr!(
+                                "无效参数",
+                                "invalid argument"
+                            )));
+                        }
+                    }
+                    i += 2;
+                } else {
+                    log_error!(
+                        "{}",
+                        tr!(
+                            "错误: --calibrate 选项需要一个参数",
+                            "Error: option --calibrate requires an argument"
+                        )
+                    );
+                    print_usage();
+                    return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
+                }
+            }
+            "--save-calibration" => {
+                save_calibration = true;
+                i += 1;
+            }
+            "--daemon" => {
+                daemon_mode = true;
+                i += 1;
+            }
+            "--pid-file" => {
+                if i + 1 < args.len() {
+                    pid_file = args[i + 1].clone();
+                    i += 2;
+                } else {
+                    log_error!(
+                        "{}",
+                        tr!(
+                            "错误: --pid-file 选项需要一个参数",
+                            "Error: option --pid-file requires an argument"
+                        )
+                    );
                     print_usage();
-                    return Err("缺少参数".into());
+                    return Err(Error::InvalidArgument(tr!("缺少参数", "missing argument")));
                 }
             }
+            "--show-config" => {
+                show_config = true;
+                i += 1;
+            }
+            "--dry-run" => {
+                overrides.dry_run = true;
+                i += 1;
+            }
+            "--debounce-ms" => {
+                overrides.debounce_time_ms = Some(millis_arg(&args, i)?);
+                i += 2;
+            }
+            "--h-debounce-ms" => {
+                overrides.h_debounce_time_ms = Some(millis_arg(&args, i)?);
+                i += 2;
+            }
+            "--timeout-ms" => {
+                overrides.debounce_timeout_ms = Some(millis_arg(&args, i)?);
+                i += 2;
+            }
             "-h" | "--help" => {
                 print_usage();
                 return Ok(());
             }
+            "-V" | "--version" => {
+                print_version();
+                return Ok(());
+            }
+            // 隐藏选项，不在帮助中列出
+            "--generate-completions" => {
+                let shell = args.get(i + 1).map(String::as_str).unwrap_or("");
+                match completions::generate(shell) {
+                    Some(script) => {
+                        print!("{}", script);
+                        return Ok(());
+                    }
+                    None => {
+                        log_error!(
+                            "{}",
+                            tr!(
+                                "错误: --generate-completions 需要指定 shell: {}",
+                                "Error: --generate-completions requires a shell: {}",
+                                completions::SHELLS.join(", ")
+                            )
+                        );
+                        return Err(Error::InvalidArgument(tr!("无效参数", "invalid argument")));
+                    }
+                }
+            }
             _ => {
-                log_error!("错误: 未知选项 '{}'", args[i]);
+                log_error!(
+                    "{}",
+                    tr!("错误: 未知选项 '{}'", "Error: unknown option '{}'", args[i])
+                );
                 print_usage();
-                return Err("未知选项".into());
+                return Err(Error::InvalidArgument(tr!("未知选项", "unknown option")));
             }
         }
     }
 
+    // 不要求 root，直接检查能否创建虚拟设备；输入设备的权限在打开时检查。
+    // 只读取设备（列出、转储、校准、试运行）和不访问设备的模式不需要 uinput
+    let creates_virtual_device = simulate_file.is_none()
+        && !show_config
+        && !create_config
+        && !list_only
+        && !dump_events
+        && calibrate_for.is_none()
+        && !overrides.dry_run;
+    if creates_virtual_device {
+        if let Err(e) = check_uinput_access() {
+            log_error!("{}", tr!("错误: {}", "Error: {}", e));
+            return Err(e);
+        }
+    }
+
+    if dump_events && smooth_all {
+        log_error!(
+            "{}",
+            tr!(
+                "错误: --dump-events 不能与 --all 同时使用",
+                "Error: --dump-events cannot be combined with --all"
+            )
+        );
+        return Err(Error::InvalidArgument(tr!("无效参数", "invalid argument")));
+    }
+
+    if calibrate_for.is_some() && (smooth_all || dump_events || daemon_mode) {
+        log_error!(
+            "{}",
+            tr!(
+                "错误: --calibrate 不能与 --all、--dump-events 或 --daemon 同时使用",
+                "Error: --calibrate cannot be combined with --all, --dump-events or --daemon"
+            )
+        );
+        return Err(Error::InvalidArgument(tr!("无效参数", "invalid argument")));
+    }
+
+    if annotated && !create_config {
+        log_error!(
+            "{}",
+            tr!(
+                "错误: --annotated 需要与 --create-config 一起使用",
+                "Error: --annotated requires --create-config"
+            )
+        );
+        return Err(Error::InvalidArgument(tr!("无效参数", "invalid argument")));
+    }
+
+    if save_calibration && calibrate_for.is_none() {
+        log_error!(
+            "{}",
+            tr!(
+                "错误: --save-calibration 需要与 --calibrate 一起使用",
+                "Error: --save-calibration requires --calibrate"
+            )
+        );
+        return Err(Error::InvalidArgument(tr!("无效参数", "invalid argument")));
+    }
+
+    if daemon_mode && (dump_events || simulate_file.is_some() || list_only || benchmark_mode) {
+        log_error!(
+            "{}",
+            tr!(
+                "错误: --daemon 不能与 --dump-events、--simulate、--list 或 --benchmark 同时使用",
+                "Error: --daemon cannot be combined with --dump-events, --simulate, --list or --benchmark"
+            )
+        );
+        return Err(Error::InvalidArgument(tr!("无效参数", "invalid argument")));
+    }
+
+    // 命令行指定的日志级别在加载配置之前生效，加载配置时的消息同样受它控制
+    if let Some(level) = cmd_log_level.as_deref().and_then(LogLevel::from_str) {
+        set_log_level(level);
+    }
+
+    // 没有指定配置文件时按环境变量、用户配置目录、/etc 的顺序查找
+    let config_path = config_path.unwrap_or_else(|| Config::default_path(!is_root()));
+
     // 创建默认配置文件（如果请求）
     if create_config {
-        Config::create_default(&config_path)?;
+        if annotated {
+            Config::create_annotated(&config_path)?;
+        } else {
+            Config::create_default(&config_path)?;
+        }
         if !list_only {
             log_info!("已创建默认配置文件，退出程序");
             return Ok(());
         }
     }
 
+    // 标准输出只留给配置内容
+    if show_config {
+        logger::set_log_target(LogTarget::Stderr);
+    }
+
     // 加载配置
-    let config = Config::load(&config_path)?;
+    let mut config = match Config::load(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            log_error!(
+                "{}",
+                tr!(
+                    "加载配置文件 {} 失败: {}",
+                    "Failed to load config file {}: {}",
+                    config_path,
+                    e
+                )
+            );
+            return Err(e);
+        }
+    };
+
+    // 命令行参数覆盖配置文件中的消抖时间
+    if let Err(e) = config.apply_overrides(overrides) {
+        log_error!(
+            "{}",
+            tr!("命令行参数错误: {}", "Invalid command-line argument: {}", e)
+        );
+        return Err(Error::InvalidArgument(e));
+    }
+
+    // 打印合并了默认值、配置文件和命令行参数后实际生效的配置
+    if show_config {
+        let contents =
+            toml::to_string_pretty(&config).map_err(|e| Error::ConfigInvalid(e.to_string()))?;
+        print!("{}", contents);
+        return Ok(());
+    }
+
+    logger::set_timestamps(config.logging.timestamps);
+    logger::set_log_target(config.logging.target);
+    // 语言名称已在加载配置时检查过
+    if let Some(language) = i18n::Language::from_str(&config.logging.language) {
+        i18n::set_language(language);
+    }
 
     // 设置日志级别 - 命令行参数优先于配置文件
     let log_level_str = cmd_log_level.unwrap_or(config.logging.level.clone());
@@ -441,39 +2622,822 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         set_log_level(LogLevel::Info);
     }
 
+    // 转储和模拟模式下标准输出只留给 CSV，校准模式只留给建议的配置，日志改到标准错误
+    if (dump_events || simulate_file.is_some() || calibrate_for.is_some())
+        && config.logging.target == LogTarget::Stdout
+    {
+        logger::set_log_target(LogTarget::Stderr);
+    }
+
+    // 输出日志到文件
+    if let Some(log_file) = &config.logging.file {
+        if let Err(e) = logger::set_log_file(log_file, config.logging.max_size_mb) {
+            log_error!(
+                "{}",
+                tr!(
+                    "错误: 无法打开日志文件 {}: {}",
+                    "Error: cannot open log file {}: {}",
+                    log_file,
+                    e
+                )
+            );
+            return Err(e.into());
+        }
+        log_info!("日志输出到文件: {}", log_file);
+    }
+
+    // 用记录的事件离线模拟消抖
+    if let Some(file) = simulate_file {
+        return simulate::run(&file, &config);
+    }
+
+    // 用合成设备测量端到端延迟
+    if benchmark_mode {
+        return benchmark::run(&config, benchmark::DEFAULT_SAMPLES);
+    }
+
     // 查找可用的鼠标设备
-    let mut devices = find_mouse_devices()?;
+    let mut devices = match find_mouse_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            log_error!("{}", tr!("错误: {}", "Error: {}", e));
+            return Err(e);
+        }
+    };
+    let found = devices.len();
 
     // 如果配置中有名称过滤器，应用过滤
-    if let Some(name_filter) = &config.device.name_filter {
-        devices.retain(|(_, name)| name.contains(name_filter));
+    let mut filter = DeviceFilter {
+        // 正则表达式已在加载配置时检查过
+        name: config
+            .device
+            .name_filter
+            .as_ref()
+            .map(NameMatcher::new)
+            .transpose()
+            .map_err(Error::ConfigInvalid)?,
+        include: config
+            .device
+            .include
+            .iter()
+            .map(|spec| DevicePattern::parse(spec))
+            .collect(),
+        exclude: config
+            .device
+            .exclude
+            .iter()
+            .map(|spec| DevicePattern::parse(spec))
+            .collect(),
+        id: None,
+        require_rel_axes: false,
+        include_touchpads: config.device.include_touchpads,
+    };
+    if !filter.include_touchpads {
+        devices.retain(|device| !device.is_touchpad);
+    }
+    // 排除列表优先于允许列表
+    if !filter.include.is_empty() || !filter.exclude.is_empty() {
+        devices.retain(|device| {
+            if filter.exclude.iter().any(|pattern| pattern.matches(device)) {
+                log_info!(
+                    "设备 {} ({}) 在 device.exclude 中，忽略",
+                    device.name,
+                    device.path
+                );
+                return false;
+            }
+            if !filter.include.is_empty()
+                && !filter.include.iter().any(|pattern| pattern.matches(device))
+            {
+                log_debug!(
+                    "设备 {} ({}) 不在 device.include 中，忽略",
+                    device.name,
+                    device.path
+                );
+                return false;
+            }
+            true
+        });
+    }
+    if let Some(name_filter) = &filter.name {
+        devices.retain(|device| filter.matches(device));
         log_info!(
-            "应用名称过滤器 '{}', 找到 {} 个匹配设备",
+            "应用名称过滤器 {}, 找到 {} 个匹配设备",
             name_filter,
             devices.len()
         );
     }
 
+    // 有 BTN_LEFT 但没有相对轴的设备可能是键盘，抓取后会吞掉按键
+    if config.device.require_rel_axes {
+        filter.require_rel_axes = true;
+        devices.retain(|device| {
+            if device.is_touchpad {
+                return true;
+            }
+            if !device.has_rel_axes {
+                if filter.name.is_some() {
+                    log_warn!(
+                        "设备 {} ({}) 匹配名称过滤器但没有相对轴，可能不是鼠标，已忽略（device.require_rel_axes = false 可以关闭此检查）",
+                        device.name,
+                        device.path
+                    );
+                } else {
+                    log_debug!("设备 {} ({}) 没有相对轴，忽略", device.name, device.path);
+                }
+            }
+            device.has_rel_axes
+        });
+    }
+
     if devices.is_empty() {
-        log_error!("错误: 未找到鼠标设备");
-        return Err("未找到鼠标设备".into());
+        if found == 0 {
+            log_error!(
+                "{}",
+                tr!(
+                    "错误: 未找到鼠标设备，/dev/input 中能打开的设备都不是鼠标或触摸板",
+                    "Error: no mouse found; none of the readable devices in /dev/input is a mouse or touchpad"
+                )
+            );
+        } else {
+            log_error!(
+                "{}",
+                tr!(
+                    "错误: 找到 {} 个鼠标设备，但都不满足设备过滤条件",
+                    "Error: found {} mouse devices, but none matches the device filters",
+                    found
+                )
+            );
+        }
+        return Err(Error::DeviceNotFound(tr!(
+            "未找到鼠标设备",
+            "no mouse found"
+        )));
     }
 
     // 如果只是列出设备，则打印并退出
     if list_only {
         log_info!("可用的鼠标设备:");
-        for (i, (path, name)) in devices.iter().enumerate() {
-            println!("{}. {} ({})", i + 1, name, path);
+        for (i, device) in devices.iter().enumerate() {
+            println!("{}. {}", i + 1, device);
+            if verbose {
+                print_capabilities(&device.path);
+            }
+        }
+        return Ok(());
+    }
+
+    let device_spec = specified_device.or(config.device.path.clone());
+
+    // 单设备模式在转入后台之前选好设备，选择失败时错误仍能输出到终端。
+    // 后台运行、--quiet 或标准输入不是终端时不询问用户，有多个候选设备时直接报错
+    let device = if smooth_all {
+        None
+    } else {
+        Some(select_device(
+            &devices,
+            device_spec.clone(),
+            !daemon_mode && !quiet && stdin_is_tty(),
+        )?)
+    };
+
+    // 转入后台，必须在创建任何线程之前进行
+    let _pid_file = if daemon_mode {
+        // 终端不再可用，没有配置日志文件时改用系统日志
+        if config.logging.file.is_none() && config.logging.target != LogTarget::Syslog {
+            log_info!("转入后台运行，日志输出到系统日志");
+            logger::set_log_target(LogTarget::Syslog);
+        }
+        if let Err(e) = daemon::daemonize() {
+            log_error!(
+                "{}",
+                tr!(
+                    "错误: 无法转入后台运行: {}",
+                    "Error: cannot daemonize: {}",
+                    e
+                )
+            );
+            return Err(e.into());
+        }
+        match daemon::PidFile::create(&pid_file) {
+            Ok(pid_file) => Some(pid_file),
+            Err(e) => {
+                log_error!(
+                    "{}",
+                    tr!(
+                        "错误: 无法写入 PID 文件: {}",
+                        "Error: cannot write PID file: {}",
+                        e
+                    )
+                );
+                return Err(e.into());
+            }
+        }
+    } else {
+        None
+    };
+
+    // 安装退出信号处理，确保 Ctrl-C 时释放设备
+    signals::install_handlers()?;
+
+    // 标准和高分辨率滚轮事件的缩放比例相差过大时提醒
+    config.warn_scale_divergence();
+
+    // 启动 Prometheus 指标服务
+    #[cfg(feature = "metrics")]
+    if config.metrics.enabled {
+        metrics::spawn_server(&config.metrics.address, config.metrics.port);
+    }
+    #[cfg(not(feature = "metrics"))]
+    if config.metrics.enabled {
+        log_warn!("未启用 metrics 特性，忽略配置文件中的指标服务");
+    }
+
+    // 启动控制套接字
+    #[cfg(feature = "ipc")]
+    if config.ipc.enabled {
+        ipc::spawn_server(&config.ipc.socket_path, &config);
+    }
+    #[cfg(not(feature = "ipc"))]
+    if config.ipc.enabled {
+        log_warn!("未启用 ipc 特性，忽略配置文件中的控制套接字");
+    }
+
+    // 配置了应用配置时监视焦点窗口
+    #[cfg(feature = "x11")]
+    if !config.profiles.is_empty() {
+        focus::spawn_watcher();
+    }
+    #[cfg(not(feature = "x11"))]
+    if !config.profiles.is_empty() {
+        log_warn!("未启用 x11 特性，忽略配置文件中的应用配置");
+    }
+
+    // 平滑所有匹配的设备
+    if smooth_all {
+        // 指定了 vendor:product 时只处理该型号的设备
+        if let Some(id) = device_spec.as_deref().and_then(parse_vendor_product) {
+            filter.id = Some(id);
+            devices.retain(|device| filter.matches(device));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        for device in devices {
+            // 接收端还在，发送不会失败
+            let _ = sender.send(device);
+        }
+
+        // 热插拔监听持有发送端，新插入的匹配设备会被送到 run_all
+        #[cfg(feature = "hotplug")]
+        hotplug::spawn_monitor(filter, sender);
+        #[cfg(not(feature = "hotplug"))]
+        drop(sender);
+
+        return MouseSmoother::run_all(receiver, &config, &config_path, run_for);
+    }
+
+    let device = device.expect("单设备模式已经选择了设备");
+
+    // 转储原始事件，不做平滑，也不自动重连
+    if dump_events {
+        let mut smoother = MouseSmoother::new(&device.path, &config, &config_path)?;
+        smoother.enable_event_dump();
+        if let Some(duration) = run_for {
+            smoother.stop_after(duration);
+        }
+        smoother.run()?;
+        return Ok(());
+    }
+
+    // 校准：正常平滑一段时间，统计抖动后给出建议参数，不自动重连
+    if let Some(duration) = calibrate_for {
+        let mut smoother = MouseSmoother::new(&device.path, &config, &config_path)?;
+        smoother.enable_calibration();
+        smoother.stop_after(duration);
+        log_info!("开始校准 {:?}，请像平时一样上下滚动滚轮", duration);
+        smoother.run()?;
+
+        let calibrator = smoother.calibrator.take().unwrap_or_default();
+        let matcher = format!("{:04x}:{:04x}", device.vendor, device.product);
+        if let Some(suggestion) = calibrator.print_report(&smoother.base_config, &matcher) {
+            if save_calibration {
+                // 写回配置文件本身的内容，不包含命令行覆盖参数
+                let mut file_config = Config::load(&config_path)?;
+                suggestion.apply(&mut file_config, &matcher);
+                file_config.save(&config_path)?;
+                log_info!(
+                    "已把建议参数写入配置文件 {} 中 {} 的设备配置（原文件中的注释不会保留）",
+                    config_path,
+                    matcher
+                );
+            }
         }
         return Ok(());
     }
 
-    // 确定要使用的设备
-    let device_path = select_device(&devices, specified_device.or(config.device.path.clone()))?;
+    // 运行主循环，设备断开后自动重连
+    MouseSmoother::run_with_reconnect(device, &config, &config_path, run_for)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::io;
+    use std::os::unix::io::RawFd;
+    use std::rc::Rc;
+
+    const SYN: EventCode = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+    const WHEEL: EventCode = EventCode::EV_REL(EV_REL::REL_WHEEL);
+    const WHEEL_HI_RES: EventCode = EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES);
+
+    /// 按顺序返回写好的事件的输入来源，读完后返回 EAGAIN
+    ///
+    /// 事件组之间相隔 8ms（125Hz），组内事件的时间相同。
+    #[derive(Default)]
+    struct ScriptedSource {
+        events: VecDeque<io::Result<InputEvent>>,
+        time: Duration,
+    }
+
+    impl ScriptedSource {
+        /// 追加一个完整的事件组
+        fn group(self, events: &[(EventCode, i32)]) -> Self {
+            self.partial(events).sync()
+        }
+
+        /// 只追加组内的事件，不结束事件组
+        fn partial(mut self, events: &[(EventCode, i32)]) -> Self {
+            for &(code, value) in events {
+                let event = InputEvent::new(&self.time_val(), &code, value);
+                self.events.push_back(Ok(event));
+            }
+            self
+        }
+
+        /// 追加 SYN_REPORT 结束当前事件组
+        fn sync(mut self) -> Self {
+            let event = InputEvent::new(&self.time_val(), &SYN, 0);
+            self.events.push_back(Ok(event));
+            self.time += Duration::from_millis(8);
+            self
+        }
+
+        /// 在当前位置读取失败
+        fn error(mut self, errno: i32) -> Self {
+            self.events
+                .push_back(Err(io::Error::from_raw_os_error(errno)));
+            self
+        }
+
+        fn time_val(&self) -> evdev_rs::TimeVal {
+            evdev_rs::TimeVal::new(self.time.as_secs() as i64, self.time.subsec_micros() as i64)
+        }
+    }
+
+    impl EventSource for ScriptedSource {
+        fn next_event(&mut self, _flags: ReadFlag) -> io::Result<(ReadStatus, InputEvent)> {
+            match self.events.pop_front() {
+                Some(event) => event.map(|event| (ReadStatus::Success, event)),
+                None => Err(io::Error::from_raw_os_error(libc::EAGAIN)),
+            }
+        }
+
+        fn raw_fd(&self) -> RawFd {
+            -1
+        }
+
+        fn name(&self) -> Option<&str> {
+            Some("Scripted Mouse")
+        }
+
+        fn vendor_id(&self) -> u16 {
+            0
+        }
+
+        fn product_id(&self) -> u16 {
+            0
+        }
+
+        fn is_grabbed(&self) -> bool {
+            false
+        }
+
+        fn release(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// 把写入的事件收集到共享的 Vec 中，交给平滑器之后测试仍然可以取出输出
+    #[derive(Clone, Default)]
+    struct SharedSink(Rc<RefCell<Vec<InputEvent>>>);
+
+    impl EventSink for SharedSink {
+        fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+            self.0.borrow_mut().write_event(event)
+        }
+    }
+
+    impl SharedSink {
+        /// 取出目前为止写入的 (事件代码, 值)
+        fn take(&self) -> Vec<(EventCode, i32)> {
+            self.0
+                .borrow_mut()
+                .drain(..)
+                .map(|event| (event.event_code, event.value))
+                .collect()
+        }
+    }
+
+    /// 用默认配置（经 `adjust` 修改）创建平滑器，输入设备有高分辨率滚轮
+    fn smoother(adjust: impl FnOnce(&mut Config)) -> (MouseSmoother, SharedSink) {
+        smoother_for(adjust, true)
+    }
+
+    /// 同 `smoother`，`has_hi_res` 指定输入设备是否有（垂直和水平）高分辨率滚轮
+    fn smoother_for(
+        adjust: impl FnOnce(&mut Config),
+        has_hi_res: bool,
+    ) -> (MouseSmoother, SharedSink) {
+        let mut config = Config::default();
+        // 平滑器使用真实时钟，放宽消抖时间，测试机偶尔的停顿不会让反向被当作新的滚动
+        config.wheel.debounce_time_ms = 1000;
+        config.wheel.h_debounce_time_ms = 1000;
+        config.wheel.debounce_timeout_ms = 2000;
+        adjust(&mut config);
+        let sink = SharedSink::default();
+        let smoother = MouseSmoother::with_source(
+            Box::new(ScriptedSource::default()),
+            Box::new(sink.clone()),
+            &config,
+            "",
+            has_hi_res,
+            has_hi_res,
+        )
+        .expect("默认配置应当有效");
+        (smoother, sink)
+    }
+
+    /// 让平滑器读完 `source` 中的所有事件
+    fn feed(smoother: &mut MouseSmoother, source: ScriptedSource) -> ReadOutcome {
+        smoother.input_device = Box::new(source);
+        smoother.read_events().expect("写入 Vec 不会失败")
+    }
+
+    /// 有高分辨率滚轮的设备滚动一格时内核报告的事件，`direction` 为 1 表示向上
+    fn detent(direction: i32) -> [(EventCode, i32); 2] {
+        [(WHEEL_HI_RES, direction * 120), (WHEEL, direction)]
+    }
+
+    #[test]
+    fn reverse_blip_in_scroll_is_filtered() {
+        let (mut smoother, sink) = smoother(|_| {});
+        let script = ScriptedSource::default()
+            .group(&detent(1))
+            .group(&detent(1))
+            .group(&detent(1))
+            .group(&detent(-1))
+            .group(&detent(1))
+            .group(&detent(1));
+        assert!(matches!(
+            feed(&mut smoother, script),
+            ReadOutcome::Drained { read_any: true }
+        ));
+
+        // 被过滤的反向整组没有输出，也不发送空的 SYN_REPORT
+        let up = [(WHEEL, 1), (WHEEL_HI_RES, 120), (SYN, 0)];
+        assert_eq!(sink.take(), [up; 5].concat());
+    }
+
+    #[test]
+    fn other_events_keep_their_group() {
+        let rel_x = EventCode::EV_REL(EV_REL::REL_X);
+        let rel_y = EventCode::EV_REL(EV_REL::REL_Y);
+        let (mut smoother, sink) = smoother(|_| {});
+        let script = ScriptedSource::default()
+            .group(&[(rel_x, 3), (rel_y, -2)])
+            .group(&detent(1))
+            .group(&[(WHEEL_HI_RES, -120), (WHEEL, -1), (rel_x, 1)]);
+        feed(&mut smoother, script);
+
+        // 滚轮被过滤时同组的指针移动照常转发，并以 SYN_REPORT 结束
+        assert_eq!(
+            sink.take(),
+            [
+                (rel_x, 3),
+                (rel_y, -2),
+                (SYN, 0),
+                (WHEEL, 1),
+                (WHEEL_HI_RES, 120),
+                (SYN, 0),
+                (rel_x, 1),
+                (SYN, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_source_reads_nothing() {
+        let (mut smoother, sink) = smoother(|_| {});
+        assert!(matches!(
+            feed(&mut smoother, ScriptedSource::default()),
+            ReadOutcome::Drained { read_any: false }
+        ));
+        assert!(sink.take().is_empty());
+    }
+
+    #[test]
+    fn scroll_right_after_start_passes() {
+        // 刚创建的平滑器没有滚动方向，启动后立即开始的滚动不会被当作反向
+        let (mut smoother, sink) = smoother(|_| {});
+        let script = ScriptedSource::default()
+            .group(&detent(-1))
+            .group(&detent(-1))
+            .group(&detent(1));
+        feed(&mut smoother, script);
+
+        let down = [(WHEEL, -1), (WHEEL_HI_RES, -120), (SYN, 0)];
+        assert_eq!(sink.take(), [down; 2].concat());
+    }
+
+    #[test]
+    fn diagonal_scroll_keeps_kernel_order_in_one_report() {
+        let rel_x = EventCode::EV_REL(EV_REL::REL_X);
+        let hwheel = EventCode::EV_REL(EV_REL::REL_HWHEEL);
+        let hwheel_hi_res = EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES);
+        let (mut smoother, sink) = smoother(|_| {});
+        let script = ScriptedSource::default()
+            .group(&[
+                (rel_x, 4),
+                (WHEEL_HI_RES, 120),
+                (WHEEL, 1),
+                (hwheel_hi_res, 120),
+                (hwheel, 1),
+            ])
+            .group(&[
+                (hwheel_hi_res, 120),
+                (hwheel, 1),
+                (WHEEL_HI_RES, -120),
+                (WHEEL, -1),
+                (rel_x, -2),
+            ]);
+        feed(&mut smoother, script);
+
+        // 每个轴的输出在该轴第一个滚轮事件的位置，三个轴都在同一个 SYN_REPORT 之前；
+        // 第二组的垂直反向被过滤，不影响同组的水平滚动和指针移动
+        assert_eq!(
+            sink.take(),
+            [
+                (rel_x, 4),
+                (WHEEL, 1),
+                (WHEEL_HI_RES, 120),
+                (hwheel, 1),
+                (hwheel_hi_res, 120),
+                (SYN, 0),
+                (hwheel, 1),
+                (hwheel_hi_res, 120),
+                (rel_x, -2),
+                (SYN, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_sources_press_target_once() {
+        let left = EventCode::EV_KEY(EV_KEY::BTN_LEFT);
+        let side = EventCode::EV_KEY(EV_KEY::BTN_SIDE);
+        let (mut smoother, sink) = smoother(|config| {
+            config
+                .button
+                .remap
+                .insert("BTN_SIDE".to_string(), "BTN_LEFT".to_string());
+        });
+        let script = ScriptedSource::default()
+            .group(&[(left, 1)])
+            .group(&[(side, 1)])
+            .group(&[(left, 0)])
+            .group(&[(side, 0)]);
+        feed(&mut smoother, script);
+
+        // 第二个来源按下和第一个来源松开都不改变虚拟按键的状态，整组没有输出；
+        // 所有来源都松开后才松开
+        assert_eq!(sink.take(), [(left, 1), (SYN, 0), (left, 0), (SYN, 0)]);
+        assert!(smoother.button_refcounts.is_empty());
+    }
+
+    #[test]
+    fn source_released_in_same_group_as_other_press() {
+        let left = EventCode::EV_KEY(EV_KEY::BTN_LEFT);
+        let side = EventCode::EV_KEY(EV_KEY::BTN_SIDE);
+        let (mut smoother, sink) = smoother(|config| {
+            config
+                .button
+                .remap
+                .insert("BTN_SIDE".to_string(), "BTN_LEFT".to_string());
+        });
+        let script = ScriptedSource::default()
+            .group(&[(side, 1)])
+            .group(&[(side, 0), (left, 1)])
+            .group(&[(left, 0)]);
+        feed(&mut smoother, script);
 
-    // 创建鼠标平滑器
-    let mut smoother = MouseSmoother::new(device_path, &config)?;
+        // 同一组里先松开一个来源再按下另一个，虚拟按键松开后重新按下
+        assert_eq!(
+            sink.take(),
+            [
+                (left, 1),
+                (SYN, 0),
+                (left, 0),
+                (left, 1),
+                (SYN, 0),
+                (left, 0),
+                (SYN, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn extreme_wheel_values_do_not_overflow() {
+        let (mut smoother, sink) = smoother(|config| {
+            config.wheel.multiplier = 3.0;
+            config.wheel.invert_vertical = true;
+        });
+        let script = ScriptedSource::default()
+            .group(&[(WHEEL_HI_RES, i32::MAX), (WHEEL, i32::MAX)])
+            .group(&[(WHEEL_HI_RES, i32::MAX), (WHEEL, i32::MAX)]);
+        feed(&mut smoother, script);
+
+        // 卡住的硬件报告极大的值时缩放、反转和刻度累积都饱和，不会溢出
+        let down = [
+            (WHEEL, -(i32::MAX / 120)),
+            (WHEEL_HI_RES, -i32::MAX),
+            (SYN, 0),
+        ];
+        assert_eq!(sink.take(), [down; 2].concat());
+    }
+
+    #[test]
+    fn inverting_min_wheel_value_saturates() {
+        let (mut smoother, sink) = smoother(|config| config.wheel.invert_vertical = true);
+        let script =
+            ScriptedSource::default().group(&[(WHEEL_HI_RES, i32::MIN), (WHEEL, i32::MIN)]);
+        feed(&mut smoother, script);
+        assert_eq!(
+            sink.take(),
+            [(WHEEL, i32::MAX / 120), (WHEEL_HI_RES, i32::MAX), (SYN, 0)]
+        );
+    }
+
+    #[test]
+    fn read_error_discards_partial_group() {
+        let rel_x = EventCode::EV_REL(EV_REL::REL_X);
+        let (mut smoother, sink) = smoother(|_| {});
+        let script = ScriptedSource::default()
+            .group(&[(rel_x, 1)])
+            .partial(&[(rel_x, 5), (WHEEL_HI_RES, 120)])
+            .error(libc::ENODEV)
+            .group(&[(rel_x, 2)]);
+        assert!(matches!(
+            feed(&mut smoother, script),
+            ReadOutcome::Disconnected
+        ));
+
+        // 完整的事件组已经发送，读到一半的事件组被丢弃，出错后不再读取
+        assert_eq!(sink.take(), [(rel_x, 1), (SYN, 0)]);
+        assert!(smoother.pending_events.is_empty());
+    }
+
+    #[test]
+    fn interrupted_read_keeps_partial_group() {
+        let rel_x = EventCode::EV_REL(EV_REL::REL_X);
+        let (mut smoother, sink) = smoother(|_| {});
+        let script = ScriptedSource::default()
+            .partial(&[(rel_x, 5)])
+            .error(libc::EINTR)
+            .partial(&[(WHEEL_HI_RES, 120), (WHEEL, 1)]);
+        assert!(matches!(
+            feed(&mut smoother, script),
+            ReadOutcome::Drained { read_any: true }
+        ));
+        assert!(sink.take().is_empty());
+
+        // EINTR 和 EAGAIN 都没有丢失数据，之后读到的 SYN_REPORT 补全同一个事件组
+        feed(&mut smoother, ScriptedSource::default().sync());
+        assert_eq!(
+            sink.take(),
+            [(rel_x, 5), (WHEEL, 1), (WHEEL_HI_RES, 120), (SYN, 0)]
+        );
+    }
+
+    #[test]
+    fn standard_events_follow_whole_detents() {
+        let (mut smoother, sink) = smoother(|_| {});
+        let script = ScriptedSource::default()
+            .group(&[(WHEEL_HI_RES, 60)])
+            .group(&[(WHEEL_HI_RES, 60), (WHEEL, 1)])
+            .group(&[(WHEEL_HI_RES, 60)])
+            .group(&[(WHEEL_HI_RES, 60), (WHEEL, 1)]);
+        feed(&mut smoother, script);
 
-    // 运行主循环
-    smoother.run()
+        // 高分辨率值原样发送，凑满一个刻度时才同时发送标准滚轮事件
+        let half = [(WHEEL_HI_RES, 60), (SYN, 0)];
+        let whole = [(WHEEL, 1), (WHEEL_HI_RES, 60), (SYN, 0)];
+        assert_eq!(sink.take(), [&half[..], &whole, &half, &whole].concat());
+    }
+
+    #[test]
+    fn multiplier_scales_both_wheel_events() {
+        let (mut smoother, sink) = smoother(|config| config.wheel.multiplier = 1.5);
+        let script = ScriptedSource::default()
+            .group(&detent(-1))
+            .group(&detent(-1));
+        feed(&mut smoother, script);
+
+        // 每格 180 个高分辨率单位，标准事件按累积的整刻度发送，两者的总距离一致
+        assert_eq!(
+            sink.take(),
+            [
+                (WHEEL, -1),
+                (WHEEL_HI_RES, -180),
+                (SYN, 0),
+                (WHEEL, -2),
+                (WHEEL_HI_RES, -180),
+                (SYN, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn horizontal_wheel_is_inverted_on_output() {
+        let hwheel = EventCode::EV_REL(EV_REL::REL_HWHEEL);
+        let hwheel_hi_res = EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES);
+        let (mut smoother, sink) = smoother(|config| config.wheel.invert_horizontal = true);
+        let script = ScriptedSource::default()
+            .group(&[(hwheel_hi_res, 120), (hwheel, 1)])
+            .group(&[(hwheel_hi_res, -120), (hwheel, -1)]);
+        feed(&mut smoother, script);
+
+        // 消抖按原始方向进行，第二组的反向被过滤，反转只作用于输出
+        assert_eq!(sink.take(), [(hwheel, -1), (hwheel_hi_res, -120), (SYN, 0)]);
+    }
+
+    #[test]
+    fn standard_only_device_gets_hi_res_events() {
+        let (mut smoother, sink) = smoother_for(|_| {}, false);
+        let script = ScriptedSource::default()
+            .group(&[(WHEEL, 1)])
+            .group(&[(WHEEL, 2)]);
+        feed(&mut smoother, script);
+
+        // 只有标准滚轮的设备按刻度数消抖，虚拟设备同时发送换算后的高分辨率事件
+        assert_eq!(
+            sink.take(),
+            [
+                (WHEEL, 1),
+                (WHEEL_HI_RES, 120),
+                (SYN, 0),
+                (WHEEL, 2),
+                (WHEEL_HI_RES, 240),
+                (SYN, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn suppressed_codes_are_dropped_with_their_scancode() {
+        let msc_scan = EventCode::EV_MSC(EV_MSC::MSC_SCAN);
+        let task = EventCode::EV_KEY(EV_KEY::BTN_TASK);
+        let left = EventCode::EV_KEY(EV_KEY::BTN_LEFT);
+        let rel_x = EventCode::EV_REL(EV_REL::REL_X);
+        let hwheel = EventCode::EV_REL(EV_REL::REL_HWHEEL);
+        let hwheel_hi_res = EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES);
+        let (mut smoother, sink) = smoother(|config| {
+            config.device.suppress_codes = ["BTN_TASK", "REL_HWHEEL", "REL_HWHEEL_HI_RES"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect();
+        });
+        let task_scan = button_scancode(EV_KEY::BTN_TASK).unwrap();
+        let left_scan = button_scancode(EV_KEY::BTN_LEFT).unwrap();
+        let script = ScriptedSource::default()
+            .group(&[(msc_scan, task_scan), (task, 1)])
+            .group(&[(msc_scan, left_scan), (left, 1)])
+            .group(&[(rel_x, 3), (hwheel_hi_res, 120), (hwheel, 1)])
+            .group(&[(msc_scan, task_scan), (task, 0)]);
+        feed(&mut smoother, script);
+
+        // 被丢弃的按键连同它的 MSC_SCAN 一起丢弃，整组没有输出时不发送 SYN_REPORT；
+        // 丢弃水平滚轮后同组的指针移动照常转发
+        assert_eq!(
+            sink.take(),
+            [
+                (msc_scan, left_scan),
+                (left, 1),
+                (SYN, 0),
+                (rel_x, 3),
+                (SYN, 0),
+            ]
+        );
+    }
 }
@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "metrics")]
+use std::io::{Read, Write};
+#[cfg(feature = "metrics")]
+use std::net::{TcpListener, TcpStream};
+#[cfg(feature = "metrics")]
+use std::thread;
+
+#[cfg(feature = "metrics")]
+use crate::{log_error, log_info, log_warn};
+
+// 所有设备共用的计数器，多设备模式下是各设备之和
+static EVENTS_READ: AtomicU64 = AtomicU64::new(0);
+static EVENTS_FORWARDED: AtomicU64 = AtomicU64::new(0);
+static JITTER_FILTERED_VERTICAL: AtomicU64 = AtomicU64::new(0);
+static JITTER_FILTERED_HORIZONTAL: AtomicU64 = AtomicU64::new(0);
+static RECONNECTS: AtomicU64 = AtomicU64::new(0);
+
+// 最近一次滚轮报告的时间（相对 START 的微秒数）和与上一次报告的间隔（微秒）
+static LAST_SCROLL_US: AtomicU64 = AtomicU64::new(0);
+static SCROLL_INTERVAL_US: AtomicU64 = AtomicU64::new(0);
+static START: OnceLock<Instant> = OnceLock::new();
+
+// 超过该时间没有滚轮报告时滚动速率记为 0
+#[cfg(any(feature = "metrics", feature = "ipc"))]
+const SCROLL_RATE_IDLE: Duration = Duration::from_secs(1);
+
+fn elapsed_us() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
+
+/// 从设备读到一个事件
+pub fn event_read() {
+    EVENTS_READ.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 向虚拟设备写入一个事件
+pub fn event_forwarded() {
+    EVENTS_FORWARDED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 一次滚轮报告被当作抖动过滤
+pub fn jitter_filtered(horizontal: bool) {
+    let counter = if horizontal {
+        &JITTER_FILTERED_HORIZONTAL
+    } else {
+        &JITTER_FILTERED_VERTICAL
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 设备断开后重新连接成功
+pub fn reconnected() {
+    RECONNECTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 记录一次滚轮报告，`interval` 为与同一轴上一次报告的间隔
+pub fn scroll_report(interval: Duration) {
+    LAST_SCROLL_US.store(elapsed_us(), Ordering::Relaxed);
+    SCROLL_INTERVAL_US.store(
+        interval.as_micros().min(u64::MAX as u128) as u64,
+        Ordering::Relaxed,
+    );
+}
+
+/// 当前滚动速率（每秒滚轮报告数），空闲时为 0
+#[cfg(any(feature = "metrics", feature = "ipc"))]
+fn scroll_rate() -> f64 {
+    let last = LAST_SCROLL_US.load(Ordering::Relaxed);
+    let interval = SCROLL_INTERVAL_US.load(Ordering::Relaxed);
+    if last == 0 || interval == 0 {
+        return 0.0;
+    }
+    if elapsed_us().saturating_sub(last) > SCROLL_RATE_IDLE.as_micros() as u64 {
+        return 0.0;
+    }
+    1_000_000.0 / interval as f64
+}
+
+/// 按 Prometheus 文本格式输出所有指标
+#[cfg(feature = "metrics")]
+fn render() -> String {
+    let mut body = String::new();
+    let counters = [
+        (
+            "mouse_smoother_events_read_total",
+            "从设备读取的事件数",
+            EVENTS_READ.load(Ordering::Relaxed),
+        ),
+        (
+            "mouse_smoother_events_forwarded_total",
+            "写入虚拟设备的事件数",
+            EVENTS_FORWARDED.load(Ordering::Relaxed),
+        ),
+        (
+            "mouse_smoother_reconnects_total",
+            "设备断开后重新连接的次数",
+            RECONNECTS.load(Ordering::Relaxed),
+        ),
+    ];
+    for (name, help, value) in counters {
+        body.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} counter\n{} {}\n",
+            name, help, name, name, value
+        ));
+    }
+
+    let name = "mouse_smoother_jitter_filtered_total";
+    body.push_str(&format!(
+        "# HELP {} 被当作抖动过滤的滚轮报告数\n# TYPE {} counter\n",
+        name, name
+    ));
+    body.push_str(&format!(
+        "{}{{axis=\"vertical\"}} {}\n",
+        name,
+        JITTER_FILTERED_VERTICAL.load(Ordering::Relaxed)
+    ));
+    body.push_str(&format!(
+        "{}{{axis=\"horizontal\"}} {}\n",
+        name,
+        JITTER_FILTERED_HORIZONTAL.load(Ordering::Relaxed)
+    ));
+
+    let name = "mouse_smoother_scroll_rate_hz";
+    body.push_str(&format!(
+        "# HELP {} 当前每秒滚轮报告数\n# TYPE {} gauge\n{} {}\n",
+        name,
+        name,
+        name,
+        scroll_rate()
+    ));
+    body
+}
+
+/// 控制套接字 status 命令输出的计数器，每行为 `名称 值`
+#[cfg(feature = "ipc")]
+pub fn summary() -> String {
+    let values = [
+        ("events_read", EVENTS_READ.load(Ordering::Relaxed).to_string()),
+        ("events_forwarded", EVENTS_FORWARDED.load(Ordering::Relaxed).to_string()),
+        (
+            "jitter_filtered_vertical",
+            JITTER_FILTERED_VERTICAL.load(Ordering::Relaxed).to_string(),
+        ),
+        (
+            "jitter_filtered_horizontal",
+            JITTER_FILTERED_HORIZONTAL.load(Ordering::Relaxed).to_string(),
+        ),
+        ("reconnects", RECONNECTS.load(Ordering::Relaxed).to_string()),
+        ("scroll_rate_hz", format!("{:.1}", scroll_rate())),
+    ];
+    values
+        .iter()
+        .map(|(name, value)| format!("{} {}\n", name, value))
+        .collect()
+}
+
+/// 启动指标 HTTP 服务线程，任何路径都返回全部指标
+#[cfg(feature = "metrics")]
+pub fn spawn_server(address: &str, port: u16) {
+    let listener = match TcpListener::bind((address, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log_error!("无法监听指标端口 {}:{}: {}", address, port, e);
+            return;
+        }
+    };
+    log_info!("指标服务监听 http://{}:{}/metrics", address, port);
+    // 初始化时间基准
+    elapsed_us();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve(stream) {
+                        log_warn!("响应指标请求失败: {}", e);
+                    }
+                }
+                Err(e) => log_warn!("接受指标连接失败: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(feature = "metrics")]
+fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    // 不解析请求，只读掉请求头；客户端迟迟不发送时不阻塞服务线程
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request);
+
+    let body = render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
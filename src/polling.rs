@@ -0,0 +1,147 @@
+use evdev_rs::TimeVal;
+use std::time::Duration;
+
+use crate::log_info;
+
+// 两次报告间隔超过该值（秒）时视为设备空闲，不计入估计
+const MAX_REPORT_GAP: f64 = 0.05;
+// 平均间隔的平滑系数
+const EMA_ALPHA: f64 = 0.05;
+// 开始使用估计值之前至少需要的样本数
+const MIN_SAMPLES: u32 = 32;
+// 归一化系数的上下限，避免估计偏差导致平滑参数失控
+const MIN_PERIODS: f64 = 0.125;
+const MAX_PERIODS: f64 = 8.0;
+
+/// 设备回报率的在线估计
+///
+/// 估计方法：以每个 SYN_REPORT 的内核时间戳作为一次报告的时间，取相邻两次报告的
+/// 间隔，丢弃超过 50ms 的间隔（设备空闲），对其余间隔做指数滑动平均，回报率即为
+/// 平均间隔的倒数。鼠标移动或滚动时设备按固定回报率连续报告，估计值会很快收敛。
+#[derive(Default)]
+pub struct PollingRateEstimator {
+    last_report: Option<f64>,
+    mean_interval: f64,
+    samples: u32,
+}
+
+impl PollingRateEstimator {
+    /// 记录一次报告
+    pub fn observe(&mut self, time: &TimeVal) {
+        let t = time.tv_sec as f64 + time.tv_usec as f64 / 1_000_000.0;
+
+        if let Some(last) = self.last_report {
+            let interval = t - last;
+            if interval > 0.0 && interval <= MAX_REPORT_GAP {
+                if self.samples == 0 {
+                    self.mean_interval = interval;
+                } else {
+                    self.mean_interval += EMA_ALPHA * (interval - self.mean_interval);
+                }
+                self.samples = self.samples.saturating_add(1);
+
+                if self.samples == MIN_SAMPLES {
+                    log_info!("估计设备回报率: {:.0} Hz", 1.0 / self.mean_interval);
+                }
+            }
+        }
+
+        self.last_report = Some(t);
+    }
+
+    /// 当前估计的回报率（Hz），样本不足时返回 None
+    pub fn rate_hz(&self) -> Option<f64> {
+        if self.samples < MIN_SAMPLES || self.mean_interval <= 0.0 {
+            return None;
+        }
+        Some(1.0 / self.mean_interval)
+    }
+
+    /// 设备的一次报告相当于 `reference_hz` 回报率下的几次报告
+    ///
+    /// 回报率越高，同样的物理滚动被拆成越多、越密集的事件，每个事件代表的时间越短。
+    /// 按事件计数的平滑（EMA）用它换算每个事件的权重，使平滑的时间常数与回报率无关；
+    /// 滚动值本身不缩放，滚动距离只取决于设备报告的值。样本不足时返回 1。
+    pub fn report_periods(&self, reference_hz: f64) -> f64 {
+        match self.rate_hz() {
+            Some(rate) => (reference_hz / rate).clamp(MIN_PERIODS, MAX_PERIODS),
+            None => 1.0,
+        }
+    }
+
+    /// 把两次滚动事件的间隔换算到 `reference_hz` 回报率下
+    ///
+    /// 参考回报率的设备两次报告至少相隔一个报告周期，高回报率设备把一次滚动拆成间隔
+    /// 更短的片段；短于一个参考周期的间隔按一个参考周期计，不会被当作更快的滚动。
+    /// 样本不足时不换算。
+    pub fn normalize_interval(&self, interval: Duration, reference_hz: f64) -> Duration {
+        if self.rate_hz().is_none() {
+            return interval;
+        }
+        interval.max(Duration::from_secs_f64(1.0 / reference_hz))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以固定间隔（微秒）记录 `count` 次报告
+    fn observe_at_rate(interval_us: i64, count: i64) -> PollingRateEstimator {
+        let mut estimator = PollingRateEstimator::default();
+        for i in 0..count {
+            let t = i * interval_us;
+            estimator.observe(&TimeVal::new(t / 1_000_000, t % 1_000_000));
+        }
+        estimator
+    }
+
+    #[test]
+    fn estimates_two_rates() {
+        let slow = observe_at_rate(8000, 100);
+        let fast = observe_at_rate(1000, 100);
+        assert!((slow.rate_hz().unwrap() - 125.0).abs() < 0.5);
+        assert!((fast.rate_hz().unwrap() - 1000.0).abs() < 0.5);
+
+        // 1000 Hz 设备的一次报告相当于 125 Hz 下的 1/8 次报告
+        assert!((slow.report_periods(125.0) - 1.0).abs() < 1e-3);
+        assert!((fast.report_periods(125.0) - 0.125).abs() < 1e-3);
+    }
+
+    #[test]
+    fn no_estimate_before_enough_samples() {
+        let estimator = observe_at_rate(1000, MIN_SAMPLES as i64);
+        assert_eq!(estimator.rate_hz(), None);
+        assert_eq!(estimator.report_periods(125.0), 1.0);
+        assert_eq!(
+            estimator.normalize_interval(Duration::from_millis(1), 125.0),
+            Duration::from_millis(1)
+        );
+    }
+
+    #[test]
+    fn idle_gaps_are_ignored() {
+        let mut estimator = PollingRateEstimator::default();
+        let mut t = 0;
+        for i in 0..100 {
+            // 每 10 次报告停顿一次，停顿不影响估计
+            t += if i % 10 == 0 { 500_000 } else { 1000 };
+            estimator.observe(&TimeVal::new(t / 1_000_000, t % 1_000_000));
+        }
+        assert!((estimator.rate_hz().unwrap() - 1000.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn dense_intervals_count_as_one_reference_period() {
+        let fast = observe_at_rate(1000, 100);
+        assert_eq!(
+            fast.normalize_interval(Duration::from_millis(1), 125.0),
+            Duration::from_millis(8)
+        );
+        // 长于参考周期的间隔是真实的停顿，不换算
+        assert_eq!(
+            fast.normalize_interval(Duration::from_millis(40), 125.0),
+            Duration::from_millis(40)
+        );
+    }
+}
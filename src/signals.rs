@@ -0,0 +1,113 @@
+use std::io;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// 收到 SIGINT/SIGTERM 后置位
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+// 每收到一次 SIGHUP 加一，各设备线程比较自己看到的代数来决定是否重新加载配置
+static RELOAD_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+// 唤醒管道：信号处理函数向写端写入一个字节，所有在 poll 中等待读端的线程都会被唤醒。
+// 读端的数据从不读出，因此退出请求之后管道一直保持可读
+static WAKEUP_READ_FD: AtomicI32 = AtomicI32::new(-1);
+static WAKEUP_WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn handle_shutdown(_signal: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+    let fd = WAKEUP_WRITE_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        let byte = 1u8;
+        // write 是异步信号安全的，失败（管道已满）也无妨
+        unsafe {
+            libc::write(fd, &byte as *const u8 as *const libc::c_void, 1);
+        }
+    }
+}
+
+extern "C" fn handle_reload(_signal: libc::c_int) {
+    RELOAD_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// 安装 SIGINT/SIGTERM/SIGHUP 处理函数
+pub fn install_handlers() -> io::Result<()> {
+    let mut fds = [0 as libc::c_int; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    WAKEUP_READ_FD.store(fds[0], Ordering::SeqCst);
+    WAKEUP_WRITE_FD.store(fds[1], Ordering::SeqCst);
+
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        // 不设置 SA_RESTART，让阻塞中的 poll 以 EINTR 返回
+        action.sa_flags = 0;
+
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            if libc::sigaction(signal, &action, ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        action.sa_sigaction = handle_reload as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        if libc::sigaction(libc::SIGHUP, &action, ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// 是否收到了退出信号
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// 请求所有设备线程重新加载配置，与收到 SIGHUP 相同
+#[cfg(feature = "ipc")]
+pub fn request_reload() {
+    RELOAD_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// 当前的配置重新加载代数
+pub fn reload_generation() -> usize {
+    RELOAD_GENERATION.load(Ordering::SeqCst)
+}
+
+/// 唤醒管道的读端，未安装信号处理时返回 None
+pub fn wakeup_fd() -> Option<RawFd> {
+    let fd = WAKEUP_READ_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        Some(fd)
+    } else {
+        None
+    }
+}
+
+/// 休眠 `duration`，收到退出信号时提前返回
+pub fn sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while !shutdown_requested() {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+        let mut pfd = libc::pollfd {
+            // 负数的 fd 会被 poll 忽略，此时相当于普通休眠
+            fd: wakeup_fd().unwrap_or(-1),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = (deadline - now)
+            .as_micros()
+            .div_ceil(1000)
+            .min(i32::MAX as u128) as i32;
+        unsafe {
+            libc::poll(&mut pfd, 1, timeout_ms);
+        }
+    }
+}
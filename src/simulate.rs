@@ -0,0 +1,214 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::clock::{Clock, MockClock};
+use crate::config::Config;
+use crate::debouncer::{DebounceParams, WheelDebouncer};
+use crate::error::Error;
+use crate::log_info;
+use crate::utils::accumulate_detents;
+
+// 假定模拟开始前滚轮已经空闲了足够久，第一个事件总是被当作新的滚动开始
+const IDLE_BEFORE_START: Duration = Duration::from_secs(3600);
+
+/// 一个滚轮轴在同一次报告中的原始值
+#[derive(Default)]
+struct AxisReport {
+    standard: i32,
+    hi_res: i32,
+}
+
+/// 一次报告（一个 SYN_REPORT）中的滚轮事件，同一次报告中的事件时间戳相同
+struct Report {
+    time: f64,
+    vertical: AxisReport,
+    horizontal: AxisReport,
+}
+
+/// 单个滚轮轴的模拟状态
+struct AxisSimulation {
+    name: &'static str,
+    debouncer: WheelDebouncer<MockClock>,
+    // 消抖时间（两个方向）都为 0 时实际运行中不做处理，原样转发
+    enabled: bool,
+    remainder: i32,
+    events: usize,
+    filtered: usize,
+}
+
+impl AxisSimulation {
+    fn new(name: &'static str, config: &Config, horizontal: bool, clock: MockClock) -> Self {
+        let params = DebounceParams::from_config(config, horizontal);
+        AxisSimulation {
+            name,
+            enabled: !params.debounce_time.get(0).is_zero(),
+            debouncer: WheelDebouncer::with_clock(params, clock),
+            remainder: 0,
+            events: 0,
+            filtered: 0,
+        }
+    }
+
+    /// 与实际运行相同：只有标准事件时按 `hi_res_step` 换算成高分辨率值，
+    /// 消抖后累积整刻度，打印一行 CSV。消抖器的时钟由调用方设置为报告的时间
+    fn feed(&mut self, report: &AxisReport, time: f64, hi_res_step: i32) {
+        if report.standard == 0 && report.hi_res == 0 {
+            return;
+        }
+
+        let input = if report.standard != 0 && report.hi_res == 0 {
+            report.standard.saturating_mul(hi_res_step)
+        } else {
+            report.hi_res
+        };
+        let output = if self.enabled {
+            self.debouncer.smooth(input)
+        } else {
+            input
+        };
+        let detents = if output != 0 {
+            accumulate_detents(&mut self.remainder, output, hi_res_step)
+        } else {
+            0
+        };
+
+        self.events += 1;
+        if output == 0 {
+            self.filtered += 1;
+        }
+        println!("{:.6},{},{},{},{}", time, self.name, input, output, detents);
+    }
+
+    /// 预热最晚放行的时间不晚于 `until` 时，在到期时刻放行缓冲的滚动，打印一行输入为 0 的 CSV。
+    /// `until` 为 None 时不论是否到期都放行（记录结束）。`start` 和 `first_time` 用于换算时间戳
+    fn release_warmup(
+        &mut self,
+        clock: &MockClock,
+        until: Option<Instant>,
+        start: Instant,
+        first_time: f64,
+        hi_res_step: i32,
+    ) {
+        let deadline = match self.debouncer.warmup_deadline() {
+            Some(deadline) if until.is_none_or(|until| deadline <= until) => deadline,
+            _ => return,
+        };
+        clock.set(deadline);
+        let output = match self.debouncer.take_warmup_due() {
+            Some(output) => output,
+            None => return,
+        };
+        let detents = accumulate_detents(&mut self.remainder, output, hi_res_step);
+        let time = first_time + deadline.duration_since(start).as_secs_f64();
+        println!("{:.6},{},0,{},{}", time, self.name, output, detents);
+    }
+}
+
+/// 解析 `--dump-events` 输出的 CSV
+///
+/// 支持 `timestamp,delta_ms,axis,value` 和 `timestamp,axis,value` 两种格式，
+/// 表头、空行和 `#` 开头的行会被忽略。
+fn parse_reports(contents: &str) -> Result<Vec<Report>, String> {
+    let mut reports: Vec<Report> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("timestamp") {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let (time, axis, value) = match fields.as_slice() {
+            [time, _, axis, value] | [time, axis, value] => (*time, *axis, *value),
+            _ => return Err(format!("第 {} 行格式错误: {}", line_no, line)),
+        };
+        let time: f64 = time
+            .parse()
+            .map_err(|_| format!("第 {} 行时间戳无效: {}", line_no, time))?;
+        let value: i32 = value
+            .parse()
+            .map_err(|_| format!("第 {} 行数值无效: {}", line_no, value))?;
+
+        let same_report = match reports.last() {
+            Some(report) => report.time == time,
+            None => false,
+        };
+        if !same_report {
+            reports.push(Report {
+                time,
+                vertical: AxisReport::default(),
+                horizontal: AxisReport::default(),
+            });
+        }
+        let report = reports.last_mut().unwrap();
+
+        match axis {
+            "REL_WHEEL" => {
+                report.vertical.standard = report.vertical.standard.saturating_add(value)
+            }
+            "REL_WHEEL_HI_RES" => {
+                report.vertical.hi_res = report.vertical.hi_res.saturating_add(value)
+            }
+            "REL_HWHEEL" => {
+                report.horizontal.standard = report.horizontal.standard.saturating_add(value)
+            }
+            "REL_HWHEEL_HI_RES" => {
+                report.horizontal.hi_res = report.horizontal.hi_res.saturating_add(value)
+            }
+            _ => return Err(format!("第 {} 行未知的滚轮轴: {}", line_no, axis)),
+        }
+    }
+
+    Ok(reports)
+}
+
+/// 把记录的滚轮事件按原有时间间隔送入消抖器，打印每次报告的处理结果
+///
+/// 输出为 CSV：`时间戳,轴,输入(高分辨率),输出(高分辨率),发送的刻度数`，输出为 0
+/// 表示被过滤或推迟，输入为 0 的行是预热到期后放行的滚动。不访问任何设备，也不需要 root 权限。回报率归一化依赖实时估计，
+/// 模拟时不做。
+pub fn run(path: &str, config: &Config) -> Result<(), Error> {
+    let contents = fs::read_to_string(path)?;
+    let reports =
+        parse_reports(&contents).map_err(|e| Error::InvalidArgument(format!("{}: {}", path, e)))?;
+    let first_time = match reports.first() {
+        Some(report) => report.time,
+        None => return Err(Error::InvalidArgument(format!("{} 中没有滚轮事件", path))),
+    };
+
+    // 两个轴共用一个模拟时钟，消抖器创建时的时间即为时钟的起点
+    let clock = MockClock::new(Instant::now());
+    let hi_res_step = config.wheel.hi_res_step;
+    let mut vertical = AxisSimulation::new("vertical", config, false, clock.clone());
+    let mut horizontal = AxisSimulation::new("horizontal", config, true, clock.clone());
+
+    // 用记录的时间戳构造合成的 Instant，保持事件之间的间隔不变
+    let start = clock.now() + IDLE_BEFORE_START;
+
+    println!("timestamp,axis,input,output,detents");
+    for report in &reports {
+        let offset = Duration::from_secs_f64((report.time - first_time).max(0.0));
+        // 开启预热时，在这次报告之前到期的缓冲滚动先按到期时间放行
+        for axis in [&mut vertical, &mut horizontal] {
+            axis.release_warmup(&clock, Some(start + offset), start, first_time, hi_res_step);
+        }
+        clock.set(start + offset);
+        vertical.feed(&report.vertical, report.time, hi_res_step);
+        horizontal.feed(&report.horizontal, report.time, hi_res_step);
+    }
+    for axis in [&mut vertical, &mut horizontal] {
+        axis.release_warmup(&clock, None, start, first_time, hi_res_step);
+    }
+
+    for axis in [&vertical, &horizontal] {
+        log_info!(
+            "{}: 共 {} 次报告，过滤 {} 次",
+            axis.name,
+            axis.events,
+            axis.filtered
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use evdev_rs::enums::{EventCode, EventType, EV_SYN};
+use evdev_rs::util::event_code_to_int;
+use evdev_rs::{
+    DeviceWrapper, EventCodeIterator, EventTypeIterator, InputEvent, InputPropIterator,
+    UInputDevice,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+use crate::{log_debug, log_info, log_warn};
+
+/// 平滑后事件的输出目标
+///
+/// 正常运行时是 uinput 虚拟设备；试运行时只记录日志；`Vec<InputEvent>` 收集写入的
+/// 事件，可以用来检查一组输入事件产生了哪些输出；`RecordingSink` 包装其他输出目标，
+/// 同时把事件录制到文件。
+pub trait EventSink {
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()>;
+}
+
+impl EventSink for UInputDevice {
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        UInputDevice::write_event(self, event)
+    }
+}
+
+/// 试运行：不创建虚拟设备，只记录将要发送的事件
+pub struct DryRunSink;
+
+impl EventSink for DryRunSink {
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        match event.event_code {
+            EventCode::EV_SYN(_) => log_debug!("[试运行] 将发送 {:?}", event.event_code),
+            _ => log_info!("[试运行] 将发送 {:?} {}", event.event_code, event.value),
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for Vec<InputEvent> {
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        self.push(event.clone());
+        Ok(())
+    }
+}
+
+/// 把写入的事件以 evemu 录制格式同时记录到文件（`debug.record_path`），再交给内层输出目标
+///
+/// 文件开头是输出设备的描述，之后每个事件一行 `E: 秒.微秒 类型 代码 值`，时间从第一个
+/// 事件开始计算。录制的文件可以用 `evemu-play` 重放：不指定设备时按描述创建一个相同的
+/// 设备，也可以重放到已有的设备上。每个 SYN_REPORT 之后刷新文件，进程异常退出时最多丢失
+/// 最后一个事件组。
+pub struct RecordingSink {
+    inner: Box<dyn EventSink>,
+    path: String,
+    // 写入失败后停止录制，事件照常交给内层输出目标
+    writer: Option<BufWriter<File>>,
+    start: Option<Instant>,
+}
+
+impl RecordingSink {
+    /// 创建录制文件（已存在时覆盖）并写入 `device` 的描述
+    pub fn create<D: DeviceWrapper>(
+        path: &str,
+        device: &D,
+        inner: Box<dyn EventSink>,
+    ) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(evemu_description(device).as_bytes())?;
+        writer.flush()?;
+        log_info!("录制输出事件到: {}", path);
+        Ok(RecordingSink {
+            inner,
+            path: path.to_string(),
+            writer: Some(writer),
+            start: None,
+        })
+    }
+
+    fn record(&mut self, event: &InputEvent) -> io::Result<()> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+        // 写入虚拟设备的事件时间由内核重新设置，这里按写入的时刻计时
+        let elapsed = self.start.get_or_insert_with(Instant::now).elapsed();
+        let (event_type, code) = event_code_to_int(&event.event_code);
+        writeln!(
+            writer,
+            "E: {}.{:06} {:04x} {:04x} {:04}",
+            elapsed.as_secs(),
+            elapsed.subsec_micros(),
+            event_type,
+            code,
+            event.value
+        )?;
+        if event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT) {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for RecordingSink {
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        self.inner.write_event(event)?;
+        if let Err(e) = self.record(event) {
+            log_warn!("写入录制文件 {} 失败，停止录制: {}", self.path, e);
+            self.writer = None;
+        }
+        Ok(())
+    }
+}
+
+/// 生成 evemu 格式的设备描述：名称、ID、输入属性位图、事件类型和代码位图、绝对坐标轴参数
+fn evemu_description<D: DeviceWrapper>(device: &D) -> String {
+    let mut description = String::from("# EVEMU 1.3\n");
+    description.push_str(&format!("N: {}\n", device.name().unwrap_or("Unknown")));
+    description.push_str(&format!(
+        "I: {:04x} {:04x} {:04x} {:04x}\n",
+        device.bustype(),
+        device.vendor_id(),
+        device.product_id(),
+        device.version()
+    ));
+
+    let properties = InputPropIterator::new()
+        .filter(|property| device.has_property(property))
+        .map(|property| property as usize);
+    push_bitmask(&mut description, "P:", properties);
+
+    // 与 EVIOCGBIT 相同，类型 0 的位图是设备支持的事件类型
+    let types: Vec<EventType> = EventTypeIterator::new()
+        .filter(|event_type| device.has_event_type(event_type))
+        .collect();
+    push_bitmask(
+        &mut description,
+        "B: 00",
+        types.iter().map(|&event_type| event_type as usize),
+    );
+    for event_type in types
+        .iter()
+        .filter(|&&event_type| event_type != EventType::EV_SYN)
+    {
+        let codes = EventCodeIterator::new(event_type)
+            .filter(|code| device.has_event_code(code))
+            .map(|code| event_code_to_int(&code).1 as usize);
+        push_bitmask(
+            &mut description,
+            &format!("B: {:02x}", *event_type as u32),
+            codes,
+        );
+    }
+
+    for code in EventCodeIterator::new(&EventType::EV_ABS) {
+        if let Some(info) = device.abs_info(&code) {
+            description.push_str(&format!(
+                "A: {:02x} {} {} {} {} {}\n",
+                event_code_to_int(&code).1,
+                info.minimum,
+                info.maximum,
+                info.fuzz,
+                info.flat,
+                info.resolution
+            ));
+        }
+    }
+    description
+}
+
+/// 把位序号写成位图，与 evemu-record 相同每行 8 个字节
+fn push_bitmask(description: &mut String, prefix: &str, bits: impl Iterator<Item = usize>) {
+    let mut bytes = vec![0u8; 8];
+    for bit in bits {
+        if bit / 8 >= bytes.len() {
+            bytes.resize((bit / 64 + 1) * 8, 0);
+        }
+        bytes[bit / 8] |= 1 << (bit % 8);
+    }
+    for line in bytes.chunks(8) {
+        description.push_str(prefix);
+        for byte in line {
+            description.push_str(&format!(" {:02x}", byte));
+        }
+        description.push('\n');
+    }
+}
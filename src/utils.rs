@@ -1,96 +1,747 @@
-use evdev_rs::{Device, DeviceWrapper};
-use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_rs::{Device, DeviceWrapper, EventCodeIterator, EventTypeIterator, GrabMode, InputEvent, InputPropIterator, ReadFlag, ReadStatus};
+use evdev_rs::enums::{int_to_bus_type, BusType, EventCode, EventType, InputProp, EV_KEY, EV_REL};
+use regex::Regex;
 use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 // 修改导入方式，从 crate 根级别导入宏
-use crate::log_info;
+use crate::{log_debug, log_info, log_warn};
+use crate::config::NameFilter;
+use crate::error::{is_permission_error, Error};
+use crate::i18n::{language, Language};
+use crate::signals;
 
 /// 检查是否有 root 权限
 pub fn is_root() -> bool {
     unsafe { libc::geteuid() == 0 }
 }
 
+/// 检查能否打开 /dev/uinput 创建虚拟设备
+///
+/// 不要求 root：通过 udev 规则把 /dev/uinput 授权给某个组（例如 input）后，组内用户同样可以运行。
+pub fn check_uinput_access() -> Result<(), Error> {
+    match std::fs::OpenOptions::new().read(true).write(true).open(UINPUT_PATH) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(Error::DeviceNotFound(format!(
+            "{} 不存在，请加载 uinput 内核模块（sudo modprobe uinput）",
+            UINPUT_PATH
+        ))),
+        Err(e) if is_permission_error(&e) => Err(Error::PermissionDenied(format!(
+            "无法打开 {} 创建虚拟设备（{}）。请使用 root 运行，或添加 udev 规则 \
+             KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\" 并把用户加入 input 组（重新登录后生效）",
+            UINPUT_PATH, e
+        ))),
+        Err(e) => Err(io::Error::new(e.kind(), format!("无法打开 {}: {}", UINPUT_PATH, e)).into()),
+    }
+}
+
+/// 打开输入设备时的错误，权限不足时说明不用 root 运行需要的设置
+pub fn open_device_error(path: &str, e: io::Error) -> Error {
+    if is_permission_error(&e) {
+        Error::PermissionDenied(format!(
+            "无法打开 {}（{}）。请使用 root 运行，或把用户加入 input 组（重新登录后生效）",
+            path, e
+        ))
+    } else {
+        io::Error::new(e.kind(), format!("无法打开 {}: {}", path, e)).into()
+    }
+}
+
+/// 标准输入是否连接到终端（systemd 或管道下不是）
+pub fn stdin_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+/// 找到的鼠标设备
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub path: String,
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    // 总线类型（BUS_USB 等）
+    pub bustype: u16,
+    // 是否有高分辨率滚轮 REL_WHEEL_HI_RES
+    pub has_hi_res: bool,
+    // 是否有 REL_X/REL_Y、REL_WHEEL 或 REL_DIAL，只有 BTN_LEFT 的可能是键盘或组合设备
+    pub has_rel_axes: bool,
+    // 是否是触摸板
+    pub is_touchpad: bool,
+    // 指向该节点的 /dev/input/by-id 符号链接，重新插拔或重启后不变
+    pub by_id: Option<String>,
+}
+
+impl std::fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({}) [{:04x}:{:04x}] {}", self.name, self.path, self.vendor, self.product, bus_name(self.bustype))?;
+        if self.has_hi_res {
+            write!(f, "，高分辨率滚轮")?;
+        }
+        if self.is_touchpad {
+            write!(f, "，触摸板")?;
+        }
+        if let Some(by_id) = &self.by_id {
+            write!(f, "\n   {}", by_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// 总线类型的名称
+fn bus_name(bustype: u16) -> String {
+    match int_to_bus_type(bustype as u32) {
+        Some(BusType::BUS_USB) => "USB".to_string(),
+        Some(BusType::BUS_BLUETOOTH) => "蓝牙".to_string(),
+        Some(BusType::BUS_I8042) => "PS/2".to_string(),
+        Some(BusType::BUS_VIRTUAL) => "虚拟设备".to_string(),
+        Some(bus) => format!("{:?}", bus).trim_start_matches("BUS_").to_string(),
+        None => format!("总线 0x{:04x}", bustype),
+    }
+}
+
+/// 打印设备支持的全部事件类型、事件代码和输入属性（--list --verbose）
+pub fn print_capabilities(path: &str) {
+    let device = match File::open(path).ok().and_then(|file| Device::new_from_file(file).ok()) {
+        Some(device) => device,
+        None => {
+            println!("   无法打开设备");
+            return;
+        }
+    };
+
+    for event_type in EventTypeIterator::new() {
+        if event_type == EventType::EV_SYN || !device.has_event_type(&event_type) {
+            continue;
+        }
+        let codes: Vec<String> = EventCodeIterator::new(&event_type)
+            .filter(|code| device.has_event_code(code))
+            .map(|code| code.to_string())
+            .collect();
+        println!("   {}: {}", event_type, codes.join(" "));
+    }
+
+    let properties: Vec<String> = InputPropIterator::new()
+        .filter(|property| device.has_property(property))
+        .map(|property| property.to_string())
+        .collect();
+    if !properties.is_empty() {
+        println!("   INPUT_PROP: {}", properties.join(" "));
+    }
+}
+
+/// 编译后的设备名称过滤器
+#[derive(Debug, Clone)]
+pub enum NameMatcher {
+    // 名称包含其中任意一个子串
+    Substrings(Vec<String>),
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    pub fn new(filter: &NameFilter) -> Result<Self, String> {
+        match filter {
+            NameFilter::Substring(name) => Ok(NameMatcher::Substrings(vec![name.clone()])),
+            NameFilter::AnyOf(names) => Ok(NameMatcher::Substrings(names.clone())),
+            NameFilter::Regex { regex } => Regex::new(regex)
+                .map(NameMatcher::Regex)
+                .map_err(|e| format!("device.name_filter 中的正则表达式无效: {}", e)),
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Substrings(names) => names.iter().any(|n| name.contains(n.as_str())),
+            NameMatcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+impl std::fmt::Display for NameMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NameMatcher::Substrings(names) => write!(f, "'{}'", names.join("' | '")),
+            NameMatcher::Regex(regex) => write!(f, "/{}/", regex.as_str()),
+        }
+    }
+}
+
+/// device.include / device.exclude 中的一项
+#[derive(Debug, Clone)]
+pub enum DevicePattern {
+    // vendor:product
+    Id(u16, u16),
+    // 名称子串
+    Name(String),
+}
+
+impl DevicePattern {
+    /// 能解析为 vendor:product 的按 ID 匹配，否则按名称子串匹配
+    pub fn parse(spec: &str) -> Self {
+        match parse_vendor_product(spec) {
+            Some((vendor, product)) => DevicePattern::Id(vendor, product),
+            None => DevicePattern::Name(spec.to_string()),
+        }
+    }
+
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        self.matches_device(&device.name, device.vendor, device.product)
+    }
+
+    /// 按名称和 vendor:product 匹配，用于已经打开、没有 `DeviceInfo` 的设备
+    pub fn matches_device(&self, name: &str, vendor: u16, product: u16) -> bool {
+        match self {
+            DevicePattern::Id(id_vendor, id_product) => {
+                vendor == *id_vendor && product == *id_product
+            }
+            DevicePattern::Name(pattern) => name.contains(pattern.as_str()),
+        }
+    }
+}
+
+/// 设备过滤条件，启动时的扫描和热插拔使用同一套规则
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFilter {
+    // 名称匹配
+    pub name: Option<NameMatcher>,
+    // 非空时必须匹配其中一项
+    pub include: Vec<DevicePattern>,
+    // 匹配其中任意一项即排除，优先于 include
+    pub exclude: Vec<DevicePattern>,
+    // vendor:product 与之相同
+    pub id: Option<(u16, u16)>,
+    // 必须有相对轴（触摸板除外）
+    pub require_rel_axes: bool,
+    // 是否接管触摸板
+    pub include_touchpads: bool,
+}
+
+impl DeviceFilter {
+    /// 设备是否满足所有已设置的条件
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        if self.exclude.iter().any(|pattern| pattern.matches(device)) {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|pattern| pattern.matches(device)) {
+            return false;
+        }
+        if let Some(name) = &self.name {
+            if !name.matches(&device.name) {
+                return false;
+            }
+        }
+        if let Some((vendor, product)) = self.id {
+            if device.vendor != vendor || device.product != product {
+                return false;
+            }
+        }
+        if device.is_touchpad {
+            return self.include_touchpads;
+        }
+        if self.require_rel_axes && !device.has_rel_axes {
+            return false;
+        }
+        true
+    }
+}
+
+/// 是否是触摸板：有手指触摸工具按键，且不是直接在屏幕上操作的触摸屏
+pub fn is_touchpad<D: DeviceWrapper>(device: &D) -> bool {
+    device.has_event_code(&EventCode::EV_KEY(EV_KEY::BTN_TOOL_FINGER))
+        && !device.has_property(&InputProp::INPUT_PROP_DIRECT)
+}
+
+// 按设备型号和序列号命名的稳定符号链接
+const BY_ID_DIR: &str = "/dev/input/by-id";
+// 输入设备目录
+const INPUT_DIR: &str = "/dev/input";
+
+const UINPUT_PATH: &str = "/dev/uinput";
+
+/// 查找指向 `path` 的 by-id 符号链接
+///
+/// 同一个节点可能有多个链接，按文件名排序后取第一个，保证每次结果相同。
+/// 没有序列号等信息的设备（例如部分 PS/2 设备）没有 by-id 链接。
+fn by_id_path(path: &Path) -> Option<String> {
+    let target = std::fs::canonicalize(path).ok()?;
+    let mut links: Vec<PathBuf> = std::fs::read_dir(BY_ID_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|link| std::fs::canonicalize(link).is_ok_and(|resolved| resolved == target))
+        .collect();
+    links.sort();
+    links.into_iter().next()?.to_str().map(String::from)
+}
+
+/// 是否是 /dev/input/eventN 节点（同一个鼠标还会有 mouseN 等节点）
+pub fn is_event_node(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("event"))
+}
+
+/// 打开一个事件设备，如果是鼠标或触摸板则返回其信息
+///
+/// 设备无法打开时返回错误，能打开但不是鼠标时返回 `Ok(None)`。
+pub fn probe_device(path: &Path) -> io::Result<Option<DeviceInfo>> {
+    let file = File::open(path)?;
+    let device = Device::new_from_file(file)?;
+
+    // 检查是否是鼠标设备，部分触摸板没有 BTN_LEFT
+    let is_touchpad = is_touchpad(&device);
+    if !is_touchpad && !device.has_event_code(&EventCode::EV_KEY(EV_KEY::BTN_LEFT)) {
+        return Ok(None);
+    }
+
+    let has_rel = |code| device.has_event_code(&EventCode::EV_REL(code));
+    let has_rel_axes = (has_rel(EV_REL::REL_X) && has_rel(EV_REL::REL_Y)) || has_rel(EV_REL::REL_WHEEL) || has_rel(EV_REL::REL_DIAL);
+
+    let path_str = match path.to_str() {
+        Some(path_str) => path_str.to_string(),
+        None => return Ok(None),
+    };
+    Ok(Some(DeviceInfo {
+        path: path_str,
+        name: device.name().unwrap_or("Unknown Mouse").to_string(),
+        vendor: device.vendor_id(),
+        product: device.product_id(),
+        bustype: device.bustype(),
+        has_hi_res: has_rel(EV_REL::REL_WHEEL_HI_RES),
+        has_rel_axes,
+        is_touchpad,
+        by_id: by_id_path(path),
+    }))
+}
+
 /// 查找鼠标设备
-pub fn find_mouse_devices() -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+///
+/// 区分三种找不到设备的情况：/dev/input 不存在或不可读、事件设备存在但全部无法打开
+/// （返回错误），以及能打开的设备中没有鼠标（返回空列表，由调用方处理）。
+/// 只有部分设备无法打开时跳过它们并给出警告。
+pub fn find_mouse_devices() -> Result<Vec<DeviceInfo>, Error> {
+    let entries = match std::fs::read_dir(INPUT_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::DeviceNotFound(format!(
+                "{} 不存在，系统中没有输入设备（在容器中运行时需要把 {} 挂载进容器）",
+                INPUT_DIR, INPUT_DIR
+            )));
+        }
+        Err(e) if is_permission_error(&e) => {
+            return Err(Error::PermissionDenied(format!("无法读取 {}: {}", INPUT_DIR, e)));
+        }
+        Err(e) => return Err(io::Error::new(e.kind(), format!("无法读取 {}: {}", INPUT_DIR, e)).into()),
+    };
+
     let mut devices = Vec::new();
-    
+    let mut nodes = 0;
+    let mut failures = Vec::new();
+
     // 遍历 /dev/input/event* 设备
-    for entry in std::fs::read_dir("/dev/input")? {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if let Some(file_name) = path.file_name() {
-            if let Some(file_name_str) = file_name.to_str() {
-                if file_name_str.starts_with("event") {
-                    let device_path = path.to_str().unwrap().to_string();
-                    
-                    // 尝试打开设备
-                    if let Ok(file) = File::open(&path) {
-                        if let Ok(device) = Device::new_from_file(file) {
-                            // 检查是否是鼠标设备
-                            if device.has_event_code(&EventCode::EV_KEY(EV_KEY::BTN_LEFT)) {
-                                let name = device.name().unwrap_or("Unknown Mouse").to_string();
-                                devices.push((device_path, name));
-                            }
-                        }
-                    }
-                }
+    for entry in entries {
+        let path = entry?.path();
+        if !is_event_node(&path) {
+            continue;
+        }
+        nodes += 1;
+        match probe_device(&path) {
+            Ok(Some(device)) => devices.push(device),
+            Ok(None) => {}
+            Err(e) => {
+                log_debug!("无法打开 {}: {}", path.display(), e);
+                failures.push(e);
             }
         }
     }
-    
+
+    if nodes > 0 && failures.len() == nodes {
+        let reason = &failures[0];
+        if is_permission_error(reason) {
+            return Err(Error::PermissionDenied(format!(
+                "{} 中有 {} 个事件设备，但都无法打开（{}）。请使用 root 运行，或把用户加入 input 组；在容器中运行时还需要授予设备访问权限（例如 --device 或设备 cgroup 规则）",
+                INPUT_DIR, nodes, reason
+            )));
+        }
+        return Err(Error::DeviceNotFound(format!(
+            "{} 中有 {} 个事件设备，但都无法打开（{}）。请检查设备文件权限和设备是否可用",
+            INPUT_DIR, nodes, reason
+        )));
+    }
+    if !failures.is_empty() {
+        log_warn!("{} 个事件设备无法打开，已跳过（--log-level debug 可以查看详情）", failures.len());
+    }
+
     Ok(devices)
 }
 
-/// 打印使用说明
+/// 查找名称为 `name` 的输入设备，返回其设备节点；无法打开的节点跳过
+fn find_device_by_name(name: &str) -> Option<String> {
+    let entries = std::fs::read_dir(INPUT_DIR).ok()?;
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_event_node(path))
+        .find(|path| {
+            File::open(path)
+                .ok()
+                .and_then(|file| Device::new_from_file(file).ok())
+                .is_some_and(|device| device.name() == Some(name))
+        })
+        .and_then(|path| path.to_str().map(String::from))
+}
+
+/// 抓取设备，失败时把常见的错误码转换为可操作的提示
+///
+/// 设备被占用时查找名为 `virtual_name` 的虚拟设备：存在说明占用者很可能是
+/// 另一个正在平滑这个设备的实例，否则多半是其他按键映射程序。
+fn grab_device(
+    device: &mut Device,
+    device_path: &str,
+    virtual_name: &str,
+) -> Result<(), Error> {
+    if let Err(e) = device.grab(GrabMode::Grab) {
+        let hint = match e.raw_os_error() {
+            Some(libc::EBUSY) => match find_device_by_name(virtual_name) {
+                Some(virtual_path) => format!(
+                    "设备已被其他进程抓取，而且已经存在虚拟设备 '{}' ({})，很可能已有另一个 mouse_smoother 实例在平滑这个设备（pgrep -a mouse_smoother 可以查看）",
+                    virtual_name, virtual_path
+                ),
+                None => format!(
+                    "设备已被其他进程抓取，可能是另一个 mouse_smoother 实例或按键映射程序（例如 input-remapper、evremap、keyd），sudo fuser -v {} 可以查看占用的进程",
+                    device_path
+                ),
+            },
+            Some(libc::EACCES) | Some(libc::EPERM) => {
+                "没有权限抓取设备，请使用 root 运行或检查设备文件权限".to_string()
+            }
+            Some(libc::ENODEV) => "抓取过程中设备已断开".to_string(),
+            _ => e.to_string(),
+        };
+        let message = format!(
+            "错误: 无法抓取设备 {} ({}): {}",
+            device.name().unwrap_or("Unknown Mouse"),
+            device_path,
+            hint
+        );
+        return Err(if is_permission_error(&e) {
+            Error::PermissionDenied(message)
+        } else {
+            Error::GrabFailed(message)
+        });
+    }
+    Ok(())
+}
+
+/// 输入设备及其抓取状态
+///
+/// 抓取成功后，无论是之后的初始化出错提前返回，还是正常退出，离开作用域时
+/// 都会释放抓取，避免物理鼠标一直处于被抓取、没有任何输出的状态。
+pub struct GrabbedDevice {
+    device: Device,
+    grabbed: bool,
+}
+
+impl GrabbedDevice {
+    /// 包装一个尚未抓取的设备
+    pub fn new(device: Device) -> Self {
+        GrabbedDevice {
+            device,
+            grabbed: false,
+        }
+    }
+
+    /// 抓取设备，失败时返回可操作的提示，`virtual_name` 用于判断设备被谁占用
+    pub fn grab(&mut self, device_path: &str, virtual_name: &str) -> Result<(), Error> {
+        grab_device(&mut self.device, device_path, virtual_name)?;
+        self.grabbed = true;
+        Ok(())
+    }
+
+    /// 释放抓取，未抓取时什么也不做
+    pub fn release(&mut self) -> io::Result<()> {
+        if !self.grabbed {
+            return Ok(());
+        }
+        self.grabbed = false;
+        self.device.grab(GrabMode::Ungrab)
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+}
+
+/// 平滑器读取输入事件的来源
+///
+/// 正常运行时是抓取的物理设备；换成按顺序返回写好的事件的来源后，不需要真实的设备
+/// 也能检查一组输入事件产生了哪些输出。
+pub trait EventSource {
+    /// 读取下一个事件，没有就绪的事件时返回 EAGAIN
+    fn next_event(&mut self, flags: ReadFlag) -> io::Result<(ReadStatus, InputEvent)>;
+    /// 用于 poll 等待可读的文件描述符
+    fn raw_fd(&self) -> RawFd;
+    /// 设备名称、厂商 ID 和产品 ID，用于匹配设备配置
+    fn name(&self) -> Option<&str>;
+    fn vendor_id(&self) -> u16;
+    fn product_id(&self) -> u16;
+    fn is_grabbed(&self) -> bool;
+    /// 释放抓取，未抓取时什么也不做
+    fn release(&mut self) -> io::Result<()>;
+}
+
+impl EventSource for GrabbedDevice {
+    fn next_event(&mut self, flags: ReadFlag) -> io::Result<(ReadStatus, InputEvent)> {
+        self.device.next_event(flags)
+    }
+
+    fn raw_fd(&self) -> RawFd {
+        self.device.file().as_raw_fd()
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.device.name()
+    }
+
+    fn vendor_id(&self) -> u16 {
+        self.device.vendor_id()
+    }
+
+    fn product_id(&self) -> u16 {
+        self.device.product_id()
+    }
+
+    fn is_grabbed(&self) -> bool {
+        GrabbedDevice::is_grabbed(self)
+    }
+
+    fn release(&mut self) -> io::Result<()> {
+        GrabbedDevice::release(self)
+    }
+}
+
+impl std::ops::Deref for GrabbedDevice {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl std::ops::DerefMut for GrabbedDevice {
+    fn deref_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+}
+
+impl Drop for GrabbedDevice {
+    fn drop(&mut self) {
+        // 设备已经断开时释放会失败，忽略即可
+        let _ = self.release();
+    }
+}
+
+/// 等待文件描述符可读
+///
+/// `timeout` 为 None 时一直等待。同时监听退出信号的唤醒管道，
+/// 返回 false 表示超时、被信号中断或收到退出请求。
+/// 设备断开时 poll 只报告 POLLERR/POLLHUP，这种情况也返回 true，
+/// 由随后的读取得到具体错误。
+pub fn wait_readable(fd: RawFd, timeout: Option<Duration>) -> io::Result<bool> {
+    let timeout_ms = match timeout {
+        // 向上取整到毫秒，避免截止时间前的忙等
+        Some(t) => t.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32,
+        None => -1,
+    };
+
+    let mut pfds = [
+        libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            // 负数的 fd 会被 poll 忽略
+            fd: signals::wakeup_fd().unwrap_or(-1),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+
+    let ret = unsafe { libc::poll(pfds.as_mut_ptr(), pfds.len() as libc::nfds_t, timeout_ms) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::EINTR) {
+            return Ok(false);
+        }
+        return Err(err);
+    }
+
+    Ok(pfds[0].revents & (libc::POLLIN | libc::POLLERR | libc::POLLHUP) != 0)
+}
+
+/// 累积高分辨率滚动值，返回凑满的整刻度数，余量保留在 `remainder` 中
+///
+/// 与 libinput 从高分辨率事件重建标准滚轮事件的方式一致：方向改变时丢弃反方向的余量。
+pub fn accumulate_detents(remainder: &mut i32, value: i32, step: i32) -> i32 {
+    if value.signum() != remainder.signum() {
+        *remainder = 0;
+    }
+    // 卡住的硬件可能持续发送极大的值，饱和而不是溢出
+    *remainder = remainder.saturating_add(value);
+    let detents = *remainder / step;
+    *remainder -= detents * step;
+    detents
+}
+
+/// 按系数缩放滚动值，小数部分累积到下一次，避免长时间滚动后距离丢失
+pub fn scale_with_carry(value: i32, factor: f64, carry: &mut f64) -> i32 {
+    let scaled = value as f64 * factor + *carry;
+    let whole = scaled.trunc();
+    *carry = scaled - whole;
+    whole.clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+// 帮助信息，增加选项时两种语言都要更新
+const USAGE_ZH: &str = r#"鼠标滚轮去抖工具
+用法:
+  sudo mouse_smoother [选项]
+
+选项:
+  -l, --list              列出所有可用的鼠标设备
+  --verbose               与 --list 一起使用时列出每个设备支持的全部事件
+  -a, --all               同时平滑所有匹配的鼠标设备
+  -d, --device <设备ID>    指定要使用的设备编号、路径（可以是 /dev/input/by-id 下的链接）或 vendor:product
+  -c, --config <文件路径>   指定配置文件路径 (默认 $MOUSE_SMOOTHER_CONFIG 或 /etc/mouse_smoother.toml)
+  --create-config         创建默认配置文件
+  --annotated             与 --create-config 一起使用，写入带注释的配置模板
+  --log-level <级别>       设置日志级别 (error, warn, info, debug, trace)
+  -q, --quiet             只输出错误，有多个设备时不询问（等同于 --log-level error）
+  --run-for <秒>           运行指定秒数后自动退出
+  --dump-events           以 CSV 格式打印原始滚轮事件（不做平滑）
+  --simulate <文件>        用 --dump-events 记录的事件离线模拟消抖（不需要 root）
+  --benchmark             用合成设备测量事件经过平滑的端到端延迟并退出
+  --calibrate <秒>         正常平滑指定秒数，统计滚轮抖动后打印建议的消抖参数
+  --save-calibration      与 --calibrate 一起使用，把建议参数写入配置文件
+  --show-config           打印合并命令行参数后实际生效的配置 (TOML) 并退出
+  --dry-run               试运行：不抓取设备、不创建虚拟设备，只记录将要发送的事件
+  --debounce-ms <毫秒>     覆盖配置文件中的垂直滚轮消抖时间
+  --h-debounce-ms <毫秒>   覆盖配置文件中的水平滚轮消抖时间
+  --timeout-ms <毫秒>      覆盖配置文件中的消抖超时时间
+  --daemon                转入后台运行
+  --pid-file <文件路径>     后台运行时写入的 PID 文件 (默认 /run/mouse_smoother.pid)
+  -V, --version           显示版本信息
+  -h, --help              显示此帮助信息
+"#;
+
+const USAGE_EN: &str = r#"Mouse wheel debouncing tool
+Usage:
+  sudo mouse_smoother [OPTIONS]
+
+Options:
+  -l, --list              List available mouse devices
+  --verbose               With --list, also list every event each device supports
+  -a, --all               Smooth all matching mouse devices at once
+  -d, --device <ID>       Device number, path (links under /dev/input/by-id work) or vendor:product
+  -c, --config <FILE>     Config file path (default $MOUSE_SMOOTHER_CONFIG or /etc/mouse_smoother.toml)
+  --create-config         Create a default config file
+  --annotated             With --create-config, write a fully commented config template
+  --log-level <LEVEL>     Set the log level (error, warn, info, debug, trace)
+  -q, --quiet             Only print errors and never prompt for a device (same as --log-level error)
+  --run-for <SECS>        Exit after running for the given number of seconds
+  --dump-events           Print raw wheel events as CSV (no smoothing)
+  --simulate <FILE>       Replay events recorded by --dump-events through the debouncer (no root needed)
+  --benchmark             Measure end-to-end smoothing latency with a synthetic device and exit
+  --calibrate <SECS>      Smooth normally for the given seconds, then print suggested debounce settings
+  --save-calibration      With --calibrate, write the suggested settings to the config file
+  --show-config           Print the effective config (TOML) after command-line overrides and exit
+  --dry-run               Dry run: do not grab the device or create a virtual device, only log events
+  --debounce-ms <MS>      Override the vertical wheel debounce time from the config file
+  --h-debounce-ms <MS>    Override the horizontal wheel debounce time from the config file
+  --timeout-ms <MS>       Override the debounce timeout from the config file
+  --daemon                Run in the background
+  --pid-file <FILE>       PID file written in daemon mode (default /run/mouse_smoother.pid)
+  -V, --version           Show version information
+  -h, --help              Show this help
+"#;
+
+/// 打印使用说明，按当前语言选择中文或英文
 pub fn print_usage() {
-    println!("鼠标滚轮去抖工具");
-    println!("用法:");
-    println!("  sudo mouse_smoother [选项]");
-    println!("");
-    println!("选项:");
-    println!("  -l, --list              列出所有可用的鼠标设备");
-    println!("  -d, --device <设备ID>    指定要使用的设备ID或路径");
-    println!("  -c, --config <文件路径>   指定配置文件路径");
-    println!("  --create-config         创建默认配置文件");
-    println!("  --log-level <级别>       设置日志级别 (error, warn, info, debug, trace)");
-    println!("  -h, --help              显示此帮助信息");
+    match language() {
+        Language::Chinese => print!("{}", USAGE_ZH),
+        Language::English => print!("{}", USAGE_EN),
+    }
+}
+
+/// 打印版本信息，编译时能取得 git 提交哈希时一并显示
+pub fn print_version() {
+    match option_env!("MOUSE_SMOOTHER_GIT_HASH") {
+        Some(hash) => println!("mouse_smoother {} ({})", env!("CARGO_PKG_VERSION"), hash),
+        None => println!("mouse_smoother {}", env!("CARGO_PKG_VERSION")),
+    }
+}
+
+/// 解析 `vendor:product` 形式的设备 ID（十六进制，例如 `046d:c52b`）
+pub fn parse_vendor_product(spec: &str) -> Option<(u16, u16)> {
+    let (vendor, product) = spec.split_once(':')?;
+    let vendor = u16::from_str_radix(vendor, 16).ok()?;
+    let product = u16::from_str_radix(product, 16).ok()?;
+    Some((vendor, product))
 }
 
 /// 根据设备规格选择设备
+///
+/// 没有指定设备且有多个候选时，`interactive` 为 true 才从标准输入询问，否则直接报错
 pub fn select_device(
-    devices: &[(String, String)], 
-    specified_device: Option<String>
-) -> Result<&str, Box<dyn std::error::Error>> {
+    devices: &[DeviceInfo], 
+    specified_device: Option<String>,
+    interactive: bool,
+) -> Result<&DeviceInfo, Error> {
     if let Some(device_spec) = specified_device {
         // 检查是否是数字（设备索引）
         if let Ok(index) = device_spec.parse::<usize>() {
             if index == 0 || index > devices.len() {
-                return Err(format!("错误: 无效的设备索引 {}", index).into());
+                return Err(Error::DeviceNotFound(format!("错误: 无效的设备索引 {}", index)));
             }
-            Ok(&devices[index - 1].0)
+            Ok(&devices[index - 1])
         } else {
-            // 检查是否是设备路径
+            // 检查是否是设备路径，by-id、by-path 等符号链接解析为实际的事件节点再比较
             if device_spec.starts_with("/dev/input/") {
+                let target = std::fs::canonicalize(&device_spec)
+                    .map_err(|e| Error::DeviceNotFound(format!("错误: 无法解析设备路径 '{}': {}", device_spec, e)))?;
                 // 验证设备是否存在于列表中
-                if let Some(device) = devices.iter().find(|(path, _)| path == &device_spec) {
-                    Ok(&device.0)
+                if let Some(device) = devices.iter().find(|d| Path::new(&d.path) == target) {
+                    Ok(device)
+                } else {
+                    Err(Error::DeviceNotFound(format!("错误: 指定的设备路径 '{}' 不是有效的鼠标设备", device_spec)))
+                }
+            } else if let Some((vendor, product)) = parse_vendor_product(&device_spec) {
+                // 按 vendor:product 匹配，同一个接收器可能有多个事件节点，取第一个
+                let mut matches = devices.iter().filter(|d| d.vendor == vendor && d.product == product);
+                if let Some(device) = matches.next() {
+                    let others = matches.count();
+                    if others > 0 {
+                        log_warn!("有 {} 个设备匹配 {}，使用第一个: {} ({})", others + 1, device_spec, device.name, device.path);
+                    }
+                    Ok(device)
                 } else {
-                    Err(format!("错误: 指定的设备路径 '{}' 不是有效的鼠标设备", device_spec).into())
+                    Err(Error::DeviceNotFound(format!("错误: 未找到 ID 为 '{}' 的鼠标设备", device_spec)))
                 }
             } else {
-                Err(format!("错误: 无效的设备规格 '{}'", device_spec).into())
+                Err(Error::InvalidArgument(format!("错误: 无效的设备规格 '{}'，应为设备编号、/dev/input/ 下的路径或 vendor:product", device_spec)))
             }
         }
     } else if devices.len() == 1 {
         // 如果只有一个设备，自动选择它
-        log_info!("自动选择唯一的鼠标设备: {} ({})", devices[0].1, devices[0].0);
-        Ok(&devices[0].0)
+        log_info!("自动选择唯一的鼠标设备: {} ({})", devices[0].name, devices[0].path);
+        Ok(&devices[0])
+    } else if !interactive {
+        Err(Error::DeviceSelection(format!("错误: 找到 {} 个鼠标设备，请使用 --device 或配置文件中的 device.path / name_filter 指定", devices.len())))
     } else {
         // 多个设备，显示列表并让用户选择
         log_info!("找到以下鼠标设备:");
-        for (i, (path, name)) in devices.iter().enumerate() {
-            println!("{}. {} ({})", i + 1, name, path);
+        for (i, device) in devices.iter().enumerate() {
+            println!("{}. {}", i + 1, device);
         }
         
         log_info!("请输入要使用的设备编号:");
@@ -99,9 +750,75 @@ pub fn select_device(
         let selection = input.trim().parse::<usize>().unwrap_or(0);
         
         if selection == 0 || selection > devices.len() {
-            return Err("无效的选择".into());
+            return Err(Error::DeviceSelection(format!("错误: 无效的选择 '{}'，请输入 1 到 {} 之间的编号", input.trim(), devices.len())));
         }
         
-        Ok(&devices[selection - 1].0)
+        Ok(&devices[selection - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(path: &str, vendor: u16, product: u16) -> DeviceInfo {
+        DeviceInfo {
+            path: path.to_string(),
+            name: "Test Mouse".to_string(),
+            vendor,
+            product,
+            bustype: 0x03,
+            has_hi_res: true,
+            has_rel_axes: true,
+            is_touchpad: false,
+            by_id: None,
+        }
+    }
+
+    #[test]
+    fn several_candidates_without_spec_is_a_selection_error() {
+        let devices = [device("/dev/input/event3", 0x046d, 0xc52b), device("/dev/input/event4", 0x1532, 0x0084)];
+        let e = select_device(&devices, None, false).unwrap_err();
+        assert!(matches!(e, Error::DeviceSelection(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn malformed_spec_is_an_invalid_argument() {
+        let devices = [device("/dev/input/event3", 0x046d, 0xc52b)];
+        let e = select_device(&devices, Some("mouse".to_string()), false).unwrap_err();
+        assert!(matches!(e, Error::InvalidArgument(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn spec_without_matching_device_is_not_found() {
+        let devices = [device("/dev/input/event3", 0x046d, 0xc52b)];
+        for spec in ["2", "1532:0084"] {
+            let e = select_device(&devices, Some(spec.to_string()), false).unwrap_err();
+            assert!(matches!(e, Error::DeviceNotFound(_)), "{}: {:?}", spec, e);
+        }
+        let selected = select_device(&devices, Some("046d:c52b".to_string()), false).unwrap();
+        assert_eq!(selected.path, "/dev/input/event3");
+    }
+
+    #[test]
+    fn open_errors_map_by_errno() {
+        let e = open_device_error("/dev/input/event3", io::Error::from_raw_os_error(libc::EACCES));
+        assert!(matches!(e, Error::PermissionDenied(_)), "{:?}", e);
+        let e = open_device_error("/dev/input/event3", io::Error::from_raw_os_error(libc::ENODEV));
+        assert!(matches!(e, Error::Io(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn extreme_wheel_values_saturate() {
+        let mut remainder = 0;
+        for _ in 0..3 {
+            assert_eq!(accumulate_detents(&mut remainder, i32::MAX, 120), i32::MAX / 120);
+            assert_eq!(remainder, i32::MAX % 120);
+        }
+        assert_eq!(accumulate_detents(&mut remainder, i32::MIN, 120), i32::MIN / 120);
+
+        let mut carry = 0.0;
+        assert_eq!(scale_with_carry(i32::MAX, 3.0, &mut carry), i32::MAX);
+        assert_eq!(scale_with_carry(i32::MIN, 3.0, &mut carry), i32::MIN);
     }
 }